@@ -353,6 +353,8 @@ pub fn run(sess: &Session, matches: &ArgMatches) -> Result<()> {
                 include_dirs: Default::default(),
                 export_incdirs: Default::default(),
                 defines: Default::default(),
+                vhdl_version: None,
+                sv_version: None,
                 files: Default::default(),
                 dependencies: Default::default(),
                 version: None,
@@ -555,6 +557,8 @@ fn get_fuse_depend_string(
             include_dirs: Default::default(),
             export_incdirs: Default::default(),
             defines: Default::default(),
+            vhdl_version: None,
+            sv_version: None,
             files: Default::default(),
             dependencies: Default::default(),
             version: None,
@@ -571,6 +575,8 @@ fn get_fuse_depend_string(
                 include_dirs: group.include_dirs.clone(),
                 export_incdirs: group.export_incdirs.clone(),
                 defines: group.defines.clone(),
+                vhdl_version: group.vhdl_version,
+                sv_version: group.sv_version,
                 files: group.files.clone(),
                 dependencies: group.dependencies.clone(),
                 version: version_string.clone(),
@@ -3,16 +3,24 @@
 
 //! The `script` subcommand.
 
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs;
+use std::io::Write;
 use std::path::Path;
 use std::path::PathBuf;
 
-use clap::builder::PossibleValue;
+use clap::builder::{PossibleValue, PossibleValuesParser};
+use clap::parser::ValueSource;
 use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
 use indexmap::{IndexMap, IndexSet};
+use quick_xml::events::{BytesDecl, BytesText, Event};
+use quick_xml::Writer;
+use tabwriter::TabWriter;
 use tera::{Context, Tera};
 use tokio::runtime::Runtime;
+use walkdir::WalkDir;
 
+use crate::config::{Manifest, SV_VERSIONS, VHDL_VERSIONS};
 use crate::error::*;
 use crate::sess::{Session, SessionIo};
 use crate::src::{SourceFile, SourceGroup};
@@ -31,6 +39,36 @@ pub fn new() -> Command {
                 .action(ArgAction::Append)
                 .value_parser(value_parser!(String)),
         )
+        .arg(
+            Arg::new("target-expr")
+                .long("target-expr")
+                .help("Only include sources that match a boolean target expression, e.g. `asic && !fpga`")
+                .num_args(1)
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("exclude-target")
+                .long("exclude-target")
+                .help("Exclude sources whose target references the given target")
+                .num_args(1)
+                .action(ArgAction::Append)
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("only-verilog")
+                .long("only-verilog")
+                .help("Only include Verilog/SystemVerilog source files")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+                .conflicts_with("only-vhdl"),
+        )
+        .arg(
+            Arg::new("only-vhdl")
+                .long("only-vhdl")
+                .help("Only include VHDL source files")
+                .num_args(0)
+                .action(ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("no-default-target")
                 .long("no-default-target")
@@ -40,32 +78,109 @@ pub fn new() -> Command {
         )
         .arg(
             Arg::new("format")
-                .help("Format of the generated script")
-                .required(true)
+                .help("Format of the generated script; several formats require `--output-dir`")
+                .required_unless_present_any([
+                    "list-formats",
+                    "print-schema",
+                    "print-targets",
+                    "order-report-json",
+                ])
+                .num_args(1..)
+                .value_parser(PossibleValuesParser::new(
+                    FORMATS
+                        .iter()
+                        .map(|format| PossibleValue::new(format.name).help(format.description)),
+                )),
+        )
+        .arg(
+            Arg::new("print-schema")
+                .long("print-schema")
+                .help("Print the JSON schema of the `template_json` output and exit")
+                .num_args(0)
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("print-targets")
+                .long("print-targets")
+                .help("Print the target specifications of all source groups, overall and per package, and exit")
+                .num_args(0)
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("list-formats")
+                .long("list-formats")
+                .help("List the available formats with their default targets and exit")
+                .num_args(0)
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no-source-cache")
+                .long("no-source-cache")
+                .help("Load the sources of all packages instead of using the source cache in `.bender`")
+                .num_args(0)
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .help("Print a summary of the source groups that would be emitted instead of the script, as `text` (default) or `json`")
+                .num_args(0..=1)
+                .default_missing_value("text")
+                .value_parser([PossibleValue::new("text"), PossibleValue::new("json")]),
+        )
+        .arg(
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .help("Write the generated script to a file instead of stdout")
                 .num_args(1)
-                .value_parser([
-                    PossibleValue::new("flist"),
-                    PossibleValue::new("flist-plus"),
-                    PossibleValue::new("vsim"),
-                    PossibleValue::new("vcs"),
-                    PossibleValue::new("verilator"),
-                    PossibleValue::new("synopsys"),
-                    PossibleValue::new("formality"),
-                    PossibleValue::new("riviera"),
-                    PossibleValue::new("genus"),
-                    PossibleValue::new("vivado"),
-                    PossibleValue::new("vivado-sim"),
-                    PossibleValue::new("precision"),
-                    PossibleValue::new("template"),
-                    PossibleValue::new("template_json"),
-                ]),
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("output-dir")
+                .long("output-dir")
+                .help("Write the script of each format to its file in the given directory, e.g. `compile.tcl` for vsim (see `--list-formats`)")
+                .num_args(1)
+                .conflicts_with("output")
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("fail-on-empty")
+                .long("fail-on-empty")
+                .help("Fail if no source files remain after filtering by target, package, and language")
+                .num_args(0)
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("split-output-by-language")
+                .long("split-output-by-language")
+                .help("Write the Verilog and VHDL sources to `compile_verilog.<ext>` and `compile_vhdl.<ext>` in the `--output-dir`")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+                .requires("output-dir")
+                .conflicts_with_all(["only-verilog", "only-vhdl"]),
         )
         .arg(
             Arg::new("relative-path")
                 .long("relative-path")
                 .num_args(0)
                 .action(ArgAction::SetTrue)
-                .help("Use relative paths (flist generation only)"),
+                .help("Use paths relative to the package root (`$ROOT` in Tcl and shell scripts)"),
+        )
+        .arg(
+            Arg::new("prefix-paths")
+                .long("prefix-paths")
+                .help("Rewrite paths inside the package root to lie under the given directory")
+                .num_args(1)
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("relative-to")
+                .long("relative-to")
+                .help("Use paths inside the package root relative to the given directory")
+                .num_args(1)
+                .conflicts_with("prefix-paths")
+                .value_parser(value_parser!(String)),
         )
         .arg(
             Arg::new("define")
@@ -76,22 +191,167 @@ pub fn new() -> Command {
                 .action(ArgAction::Append)
                 .value_parser(value_parser!(String)),
         )
+        .arg(
+            Arg::new("incdir")
+                .long("incdir")
+                .help("Add an include directory, relative to the package root, ahead of those of all source groups")
+                .num_args(1)
+                .action(ArgAction::Append)
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("define-file")
+                .long("define-file")
+                .help("Read additional defines (`NAME` or `NAME=VALUE`, one per line) from a file")
+                .num_args(1)
+                .action(ArgAction::Append)
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("param")
+                .long("param")
+                .help("Override a top-level parameter (NAME=VALUE), as `-g` for vopt (vsim), `-pvalue+` (vcs-filelist), or `-G` (verilator)")
+                .num_args(1)
+                .action(ArgAction::Append)
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("tool-arg")
+                .long("tool-arg")
+                .help("Pass an argument to the given tool's commands (TOOL:ARG), e.g. `vsim:-timescale=1ns/1ps`")
+                .num_args(1)
+                .action(ArgAction::Append)
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("verilator-arg")
+                .long("verilator-arg")
+                .help("Pass an argument to Verilator (verilator-config only)")
+                .num_args(1)
+                .action(ArgAction::Append)
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("part")
+                .long("part")
+                .help("Xilinx part to create the project for (vivado-project only)")
+                .num_args(1)
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("project-name")
+                .long("project-name")
+                .help("Name of the created project, defaults to the package name (vivado-project only)")
+                .num_args(1)
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("spyglass-goal")
+                .long("spyglass-goal")
+                .help("Goal to run after reading the design, e.g. `lint/lint_rtl` (spyglass only)")
+                .num_args(1)
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("lec-side")
+                .long("lec-side")
+                .help("Design side to read the sources into (conformal only)")
+                .num_args(1)
+                .default_value("golden")
+                .value_parser([
+                    PossibleValue::new("golden"),
+                    PossibleValue::new("revised"),
+                ]),
+        )
+        .arg(
+            Arg::new("top")
+                .long("top")
+                .help("Name of the top-level module to elaborate")
+                .num_args(1)
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("preserve-define-case")
+                .long("preserve-define-case")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+                .help("Do not convert define names to uppercase (`TARGET_*` defines are always uppercase)"),
+        )
         .arg(
             Arg::new("vcom-arg")
                 .long("vcom-arg")
-                .help("Pass an argument to vcom calls (vsim/vhdlan/riviera only)")
+                .help("Pass an argument to vcom calls (vsim/vhdlan/riviera/xcelium/dsim/xsim only)")
                 .num_args(1..)
                 .action(ArgAction::Append)
                 .value_parser(value_parser!(String)),
         )
+        .arg(
+            Arg::new("vcom-version")
+                .long("vcom-version")
+                .help("Select the VHDL standard for groups that do not specify one (default: 2008)")
+                .num_args(1)
+                .value_parser(VHDL_VERSIONS.to_vec()),
+        )
         .arg(
             Arg::new("vlog-arg")
                 .long("vlog-arg")
-                .help("Pass an argument to vlog calls (vsim/vlogan/riviera only)")
+                .help("Pass an argument to vlog calls (vsim/vlogan/riviera/xcelium/dsim/xsim only)")
+                .num_args(1..)
+                .action(ArgAction::Append)
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("vlog-suppress")
+                .long("vlog-suppress")
+                .help("Suppress the given message numbers in vlog calls, comma-separated (vsim/riviera only)")
+                .num_args(1)
+                .action(ArgAction::Append)
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("vcom-suppress")
+                .long("vcom-suppress")
+                .help("Suppress the given message numbers in vcom calls, comma-separated (vsim/riviera only)")
+                .num_args(1)
+                .action(ArgAction::Append)
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("compile-log")
+                .long("compile-log")
+                .help("Write the output of the compile calls to the given log file (vsim/vcs/xcelium only)")
+                .num_args(1)
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("vhdl-relaxed")
+                .long("vhdl-relaxed")
+                .help("Relax the VHDL language rules in VHDL compile calls (vsim/riviera/xcelium/xsim/ghdl only)")
+                .num_args(0)
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("timescale")
+                .long("timescale")
+                .help("Set the default timescale of Verilog compile calls, e.g. `1ns/1ps` (vsim/vcs/riviera/xcelium only)")
+                .num_args(1)
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("read-verilog-arg")
+                .long("read-verilog-arg")
+                .help("Pass an argument to read_verilog calls (yosys/ghdl-yosys only)")
                 .num_args(1..)
                 .action(ArgAction::Append)
                 .value_parser(value_parser!(String)),
         )
+        .arg(
+            Arg::new("vlog-version")
+                .long("vlog-version")
+                .help("Select the SystemVerilog standard for groups that do not specify one")
+                .num_args(1)
+                .value_parser(SV_VERSIONS.to_vec()),
+        )
         .arg(
             Arg::new("only-defines")
                 .long("only-defines")
@@ -99,6 +359,36 @@ pub fn new() -> Command {
                 .action(ArgAction::SetTrue)
                 .help("Only output commands to define macros (Vivado only)"),
         )
+        .arg(
+            Arg::new("no-defines")
+                .long("no-defines")
+                .help("Do not emit any defines, including the `TARGET_*` defines, e.g. if they are managed by the tool environment")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["define", "define-file", "only-defines"]),
+        )
+        .arg(
+            Arg::new("no-target-defines")
+                .long("no-target-defines")
+                .help("Do not emit the `TARGET_*` defines of the active targets")
+                .num_args(0)
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("define-prefix")
+                .long("define-prefix")
+                .help("Prepend a prefix to the name of every emitted define, e.g. to avoid collisions between IPs")
+                .num_args(1)
+                .value_name("PREFIX"),
+        )
+        .arg(
+            Arg::new("no-incdirs")
+                .long("no-incdirs")
+                .help("Do not emit any include directories, e.g. if they are provided by the tool environment")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+                .conflicts_with("only-includes"),
+        )
         .arg(
             Arg::new("only-includes")
                 .long("only-includes")
@@ -120,6 +410,35 @@ pub fn new() -> Command {
                 .action(ArgAction::SetTrue)
                 .help("Do not change `simset` fileset (Vivado only)"),
         )
+        .arg(
+            Arg::new("header-glob")
+                .long("header-glob")
+                .help("File name pattern of additional Verilog headers, which are only used for include directories")
+                .num_args(1)
+                .action(ArgAction::Append)
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("elaborate-tops")
+                .long("elaborate-tops")
+                .help("Elaborate the top-level module of each package, as given by `package.top` in its manifest (vsim/genus/jaspergold only)")
+                .num_args(0)
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("vhdl-incdirs")
+                .long("vhdl-incdirs")
+                .help("Add include directories to the search path of VHDL groups as well (synopsys/formality only)")
+                .num_args(0)
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("files-per-command")
+                .long("files-per-command")
+                .help("Split `add_files` calls into chunks of at most this many files (Vivado only)")
+                .num_args(1)
+                .value_parser(value_parser!(u64).range(1..)),
+        )
         .arg(
             Arg::new("vlogan-bin")
                 .long("vlogan-bin")
@@ -136,6 +455,37 @@ pub fn new() -> Command {
                 .default_value("vhdlan")
                 .value_parser(value_parser!(String)),
         )
+        .arg(
+            Arg::new("xvlog-bin")
+                .long("xvlog-bin")
+                .help("Specify an `xvlog` command")
+                .num_args(1)
+                .default_value("xvlog")
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("xvhdl-bin")
+                .long("xvhdl-bin")
+                .help("Specify an `xvhdl` command")
+                .num_args(1)
+                .default_value("xvhdl")
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("dsim-bin")
+                .long("dsim-bin")
+                .help("Specify the directory containing the `dvlcom` and `dvhcom` commands (dsim only)")
+                .num_args(1)
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("ghdl-std")
+                .long("ghdl-std")
+                .help("Specify the VHDL standard passed to `ghdl` (ghdl/ghdl-yosys only)")
+                .num_args(1)
+                .default_value("08")
+                .value_parser(value_parser!(String)),
+        )
         .arg(
             Arg::new("no-abort-on-error")
                 .long("no-abort-on-error")
@@ -143,6 +493,19 @@ pub fn new() -> Command {
                 .action(ArgAction::SetTrue)
                 .help("Do not abort analysis/compilation on first caught error (only for programs that support early aborting)")
         )
+        .arg(
+            Arg::new("abort-behavior")
+                .long("abort-behavior")
+                .help("Choose how Tcl scripts abort on the first caught error: `return` for scripts that are sourced by a caller, `exit` for top-level scripts, or `none` (same as `--no-abort-on-error`)")
+                .num_args(1)
+                .default_value("return")
+                .conflicts_with("no-abort-on-error")
+                .value_parser([
+                    PossibleValue::new("return"),
+                    PossibleValue::new("exit"),
+                    PossibleValue::new("none"),
+                ]),
+        )
         .arg(
             Arg::new("compilation_mode")
                 .long("compilation-mode")
@@ -155,89 +518,1057 @@ pub fn new() -> Command {
                 ])
         )
         .arg(
-            Arg::new("package")
-                .short('p')
-                .long("package")
-                .help("Specify package to show sources for")
+            Arg::new("incdir-order")
+                .long("incdir-order")
+                .help("Order the include directories of all groups by occurrence instead of alphabetically: first/last/manifest")
                 .num_args(1)
-                .action(ArgAction::Append)
-                .value_parser(value_parser!(String)),
+                .value_parser([
+                    PossibleValue::new("first"),
+                    PossibleValue::new("last"),
+                    PossibleValue::new("manifest"),
+                ]),
         )
         .arg(
-            Arg::new("no_deps")
-                .short('n')
-                .long("no-deps")
+            Arg::new("scope-incdirs")
+                .long("scope-incdirs")
                 .num_args(0)
                 .action(ArgAction::SetTrue)
-                .help("Exclude all dependencies, i.e. only top level or specified package(s)"),
+                .overrides_with("no-scope-incdirs")
+                .help("Only add the exported include directories of dependencies to the include directories of all groups (default)"),
         )
         .arg(
-            Arg::new("exclude")
-                .short('e')
-                .long("exclude")
-                .help("Specify package to exclude from sources")
+            Arg::new("no-scope-incdirs")
+                .long("no-scope-incdirs")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+                .overrides_with("scope-incdirs")
+                .help("Also add the private include directories of dependencies to the include directories of all groups"),
+        )
+        .arg(
+            Arg::new("sort-files")
+                .long("sort-files")
+                .help("Choose the order in which files are emitted: manifest/alphabetical")
+                .num_args(1)
+                .default_value("manifest")
+                .value_parser([
+                    PossibleValue::new("manifest"),
+                    PossibleValue::new("alphabetical"),
+                ]),
+        )
+        .arg(
+            Arg::new("comment-style")
+                .long("comment-style")
+                .help("Prefix of comment lines in the generated script, e.g. `//` [default: `#`]")
+                .num_args(1)
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("prepend-file")
+                .long("prepend-file")
+                .help("Insert the contents of the given file at the start of the generated script")
                 .num_args(1)
                 .action(ArgAction::Append)
                 .value_parser(value_parser!(String)),
         )
         .arg(
-            Arg::new("template")
-                .long("template")
-                .required_if_eq("format", "template")
-                .help("Path to a file containing the tera template string to be formatted.")
+            Arg::new("append-file")
+                .long("append-file")
+                .help("Insert the contents of the given file at the end of the generated script")
                 .num_args(1)
+                .action(ArgAction::Append)
                 .value_parser(value_parser!(String)),
         )
-}
-
-fn get_package_strings<I>(packages: I) -> IndexSet<String>
-where
-    I: IntoIterator,
-    I::Item: AsRef<str>,
-{
-    packages
-        .into_iter()
-        .map(|t| t.as_ref().to_string().to_lowercase())
-        .collect()
-}
-
-/// Execute the `script` subcommand.
-pub fn run(sess: &Session, matches: &ArgMatches) -> Result<()> {
-    let rt = Runtime::new()?;
-    let io = SessionIo::new(sess);
-    let mut srcs = rt.block_on(io.sources())?;
-
-    // Format-specific target specifiers.
-    let vivado_targets = &["vivado", "fpga", "xilinx"];
-    fn concat<T: Clone>(a: &[T], b: &[T]) -> Vec<T> {
-        a.iter().chain(b).cloned().collect()
-    }
-    let format = matches.get_one::<String>("format").unwrap();
-    let format_targets: Vec<&str> = if !matches.get_flag("no-default-target") {
-        match format.as_str() {
-            "flist" => vec!["flist"],
-            "flist-plus" => vec!["flist"],
-            "vsim" => vec!["vsim", "simulation"],
-            "vcs" => vec!["vcs", "simulation"],
-            "verilator" => vec!["verilator", "synthesis"],
-            "synopsys" => vec!["synopsys", "synthesis"],
-            "formality" => vec!["synopsys", "synthesis", "formality"],
-            "riviera" => vec!["riviera", "simulation"],
-            "genus" => vec!["genus", "synthesis"],
-            "vivado" => concat(vivado_targets, &["synthesis"]),
-            "vivado-sim" => concat(vivado_targets, &["simulation"]),
-            "precision" => vec!["precision", "fpga", "synthesis"],
-            "template" => vec![],
-            "template_json" => vec![],
-            _ => unreachable!(),
-        }
-    } else {
-        vec![]
-    };
-
-    // Filter the sources by target.
-    let targets = matches
-        .get_many::<String>("target")
+        .arg(
+            Arg::new("strip-comments")
+                .long("strip-comments")
+                .help("Remove comment lines and repeated blank lines from the generated script")
+                .num_args(0)
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("group-separator")
+                .long("group-separator")
+                .help("Line to insert between source groups, with `{package}` replaced by the package name (vsim/vcs only)")
+                .num_args(1)
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("annotate")
+                .long("annotate")
+                .help("Precede the files of each source group with comments naming its package, defines, and include directories (flist only)")
+                .num_args(0)
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("gen-timestamp")
+                .long("gen-timestamp")
+                .help("Add the bender version and generation time to the script header")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+                .overrides_with("no-gen-timestamp"),
+        )
+        .arg(
+            Arg::new("no-gen-timestamp")
+                .long("no-gen-timestamp")
+                .help("Keep the script header constant (default)")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+                .overrides_with("gen-timestamp"),
+        )
+        .arg(
+            Arg::new("sv-pkg-first")
+                .long("sv-pkg-first")
+                .help("Move SystemVerilog package files to the front of their compile group")
+                .num_args(0)
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("pkg-file-glob")
+                .long("pkg-file-glob")
+                .help("File name pattern of SystemVerilog package files for `--sv-pkg-first`")
+                .num_args(1)
+                .action(ArgAction::Append)
+                .default_values(["*_pkg.sv", "*_package.sv"])
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("manifest-order-report")
+                .long("manifest-order-report")
+                .help("Print the resolved compile order of each source group to stderr")
+                .num_args(0)
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("order-report-json")
+                .long("order-report-json")
+                .help("Print the dependencies of each package and their topological compile order as JSON, instead of a script")
+                .num_args(0)
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("library")
+                .long("library")
+                .help("Compile into the given library instead of `work` (vsim/qrun/vcs/riviera/ghdl/dsim/xsim/synopsys only)")
+                .num_args(1)
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("library-per-package")
+                .long("library-per-package")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+                .help("Compile each package into its own `lib_<package>` library (separate compilation mode only)"),
+        )
+        .arg(
+            Arg::new("vhdl-lib-map")
+                .long("vhdl-lib-map")
+                .help("Compile the VHDL sources of a package into the given library (PACKAGE=LIB, vsim/vcs/riviera/active-hdl in separate compilation mode only)")
+                .num_args(1)
+                .action(ArgAction::Append)
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("package")
+                .short('p')
+                .long("package")
+                .help("Specify package to show sources for")
+                .num_args(1)
+                .action(ArgAction::Append)
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("no_deps")
+                .short('n')
+                .long("no-deps")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+                .help("Exclude all dependencies, i.e. only top level or specified package(s)"),
+        )
+        .arg(
+            Arg::new("depth")
+                .long("depth")
+                .help("Only include dependencies up to the given depth below the top level or specified package(s); 0 is the same as --no-deps")
+                .num_args(1)
+                .conflicts_with("no_deps")
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("exclude")
+                .short('e')
+                .long("exclude")
+                .help("Specify package to exclude from sources")
+                .num_args(1)
+                .action(ArgAction::Append)
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("template")
+                .long("template")
+                .required_if_eq("format", "template")
+                .help("Path to a file containing the tera template string to be formatted.")
+                .num_args(1)
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("compact")
+                .long("compact")
+                .num_args(0)
+                .action(ArgAction::SetTrue)
+                .help("Print the template_json output on a single line"),
+        )
+        .arg(
+            Arg::new("template-dir")
+                .long("template-dir")
+                .help("Directory of tera templates that the custom template can include or extend")
+                .num_args(1)
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("context")
+                .long("context")
+                .help("Pass an additional string variable to the template (KEY=VALUE)")
+                .num_args(1)
+                .action(ArgAction::Append)
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            Arg::new("context-json")
+                .long("context-json")
+                .help("Pass an additional JSON variable to the template (KEY=JSON)")
+                .num_args(1)
+                .action(ArgAction::Append)
+                .value_parser(value_parser!(String)),
+        )
+}
+
+fn get_package_strings<I>(packages: I) -> IndexSet<String>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    packages
+        .into_iter()
+        .map(|t| t.as_ref().to_string().to_lowercase())
+        .collect()
+}
+
+/// A script format of `bender script`.
+struct ScriptFormat {
+    /// The name of the format, as passed on the command line.
+    name: &'static str,
+    /// A short description, as shown by `--help` and `--list-formats`.
+    description: &'static str,
+    /// The built-in template. Formats without one are serialized from the
+    /// template context, or rendered from the `--template` file.
+    template: Option<&'static str>,
+    /// The targets the format adds by default, unless `--no-default-target`
+    /// is given.
+    default_targets: &'static [&'static str],
+    /// The name of the file the script is written to with `--output-dir`.
+    file_name: &'static str,
+    /// The language the format cannot handle, whose files are omitted with
+    /// a warning.
+    unsupported: Option<SourceType>,
+    /// The format-specific options the format uses, see `FORMAT_OPTIONS`.
+    options: &'static [&'static str],
+}
+
+/// The options that only apply to some formats. Formats that do not use an
+/// option ignore it, and an error is raised if none of the requested formats
+/// uses it.
+static FORMAT_OPTIONS: &[&str] = &[
+    "annotate",
+    "vcom-arg",
+    "vlog-arg",
+    "vlog-suppress",
+    "vcom-suppress",
+    "vhdl-relaxed",
+    "timescale",
+    "compile-log",
+    "group-separator",
+    "param",
+    "vhdl-lib-map",
+    "elaborate-tops",
+    "top",
+    "dsim-bin",
+    "verilator-arg",
+    "vhdl-incdirs",
+    "spyglass-goal",
+    "read-verilog-arg",
+    "files-per-command",
+    "only-defines",
+    "only-includes",
+    "only-sources",
+    "no-simset",
+    "part",
+    "project-name",
+    "context",
+    "context-json",
+    "template-dir",
+    "compact",
+];
+
+/// All formats of `bender script`.
+static FORMATS: &[ScriptFormat] = &[
+    ScriptFormat {
+        name: "flist",
+        description: "File list",
+        template: Some(include_str!("../script_fmt/flist.tera")),
+        default_targets: &["flist"],
+        file_name: "sources.f",
+        unsupported: None,
+        options: &["annotate"],
+    },
+    ScriptFormat {
+        name: "flist-plus",
+        description: "File list with include directories and defines",
+        template: Some(include_str!("../script_fmt/flist-plus.tera")),
+        default_targets: &["flist"],
+        file_name: "sources.f",
+        unsupported: None,
+        options: &[],
+    },
+    ScriptFormat {
+        name: "vsim",
+        description: "Tcl compilation script for ModelSim/Questa",
+        template: Some(include_str!("../script_fmt/vsim_tcl.tera")),
+        default_targets: &["vsim", "simulation"],
+        file_name: "compile.tcl",
+        unsupported: None,
+        options: &[
+            "vcom-arg",
+            "vlog-arg",
+            "vlog-suppress",
+            "vcom-suppress",
+            "vhdl-relaxed",
+            "timescale",
+            "compile-log",
+            "group-separator",
+            "param",
+            "vhdl-lib-map",
+            "elaborate-tops",
+            "top",
+        ],
+    },
+    ScriptFormat {
+        name: "qrun",
+        description: "File list for Siemens Questa qrun",
+        template: Some(include_str!("../script_fmt/qrun_filelist.tera")),
+        default_targets: &["questa", "simulation"],
+        file_name: "compile.f",
+        unsupported: None,
+        options: &[],
+    },
+    ScriptFormat {
+        name: "vcs",
+        description: "Shell compilation script for Synopsys VCS",
+        template: Some(include_str!("../script_fmt/vcs_sh.tera")),
+        default_targets: &["vcs", "simulation"],
+        file_name: "compile.sh",
+        unsupported: None,
+        options: &[
+            "vcom-arg",
+            "vlog-arg",
+            "timescale",
+            "compile-log",
+            "group-separator",
+            "vhdl-lib-map",
+        ],
+    },
+    ScriptFormat {
+        name: "vcs-filelist",
+        description: "File list for Synopsys VCS (Verilog only)",
+        template: Some(include_str!("../script_fmt/vcs_filelist.tera")),
+        default_targets: &["vcs", "simulation"],
+        file_name: "compile.f",
+        unsupported: Some(SourceType::Vhdl),
+        options: &["param"],
+    },
+    ScriptFormat {
+        name: "xcelium",
+        description: "Shell compilation script for Cadence Xcelium",
+        template: Some(include_str!("../script_fmt/xcelium_sh.tera")),
+        default_targets: &["xcelium", "simulation"],
+        file_name: "compile.sh",
+        unsupported: None,
+        options: &[
+            "vcom-arg",
+            "vlog-arg",
+            "vhdl-relaxed",
+            "timescale",
+            "compile-log",
+        ],
+    },
+    ScriptFormat {
+        name: "dsim",
+        description: "Shell compilation script for Metrics DSim",
+        template: Some(include_str!("../script_fmt/dsim_sh.tera")),
+        default_targets: &["dsim", "simulation"],
+        file_name: "compile.sh",
+        unsupported: None,
+        options: &["vcom-arg", "vlog-arg", "dsim-bin"],
+    },
+    ScriptFormat {
+        name: "xsim",
+        description: "Shell compilation script for the Xilinx Vivado simulator",
+        template: Some(include_str!("../script_fmt/xsim_sh.tera")),
+        default_targets: &["xsim", "simulation"],
+        file_name: "compile.sh",
+        unsupported: None,
+        options: &["vcom-arg", "vlog-arg", "vhdl-relaxed"],
+    },
+    ScriptFormat {
+        name: "iverilog",
+        description: "Command file for Icarus Verilog (Verilog only)",
+        template: Some(include_str!("../script_fmt/iverilog.tera")),
+        default_targets: &["iverilog", "simulation"],
+        file_name: "compile.f",
+        unsupported: Some(SourceType::Vhdl),
+        options: &[],
+    },
+    ScriptFormat {
+        name: "surelog",
+        description: "Command file for Surelog (Verilog only)",
+        template: Some(include_str!("../script_fmt/surelog.tera")),
+        default_targets: &["surelog", "synthesis"],
+        file_name: "compile.f",
+        unsupported: Some(SourceType::Vhdl),
+        options: &["top"],
+    },
+    ScriptFormat {
+        name: "verilator",
+        description: "Command line arguments for Verilator",
+        template: Some(include_str!("../script_fmt/verilator_sh.tera")),
+        default_targets: &["verilator", "synthesis"],
+        file_name: "compile.sh",
+        unsupported: None,
+        options: &["param"],
+    },
+    ScriptFormat {
+        name: "verilator-config",
+        description: "Option file for Verilator",
+        template: Some(include_str!("../script_fmt/verilator_vc.tera")),
+        default_targets: &["verilator", "synthesis"],
+        file_name: "compile.f",
+        unsupported: None,
+        options: &["verilator-arg"],
+    },
+    ScriptFormat {
+        name: "verilator-make",
+        description: "Makefile fragment for Verilator",
+        template: Some(include_str!("../script_fmt/verilator_make.tera")),
+        default_targets: &["verilator", "synthesis"],
+        file_name: "compile.mk",
+        unsupported: Some(SourceType::Vhdl),
+        options: &["top"],
+    },
+    ScriptFormat {
+        name: "synopsys",
+        description: "Tcl compilation script for Synopsys DC and DE",
+        template: Some(include_str!("../script_fmt/synopsys_tcl.tera")),
+        default_targets: &["synopsys", "synthesis"],
+        file_name: "compile.tcl",
+        unsupported: None,
+        options: &["vhdl-incdirs", "top"],
+    },
+    ScriptFormat {
+        name: "formality",
+        description: "Tcl compilation script for Synopsys Formality",
+        template: Some(include_str!("../script_fmt/formality_tcl.tera")),
+        default_targets: &["synopsys", "synthesis", "formality"],
+        file_name: "compile.tcl",
+        unsupported: None,
+        options: &["vhdl-incdirs"],
+    },
+    ScriptFormat {
+        name: "spyglass",
+        description: "Tcl project file for Synopsys SpyGlass",
+        template: Some(include_str!("../script_fmt/spyglass_tcl.tera")),
+        default_targets: &["spyglass", "lint", "synthesis"],
+        file_name: "compile.tcl",
+        unsupported: None,
+        options: &["spyglass-goal"],
+    },
+    ScriptFormat {
+        name: "conformal",
+        description: "Tcl read script for Cadence Conformal LEC",
+        template: Some(include_str!("../script_fmt/conformal_tcl.tera")),
+        default_targets: &["conformal", "lec", "synthesis"],
+        file_name: "compile.tcl",
+        unsupported: None,
+        options: &[],
+    },
+    ScriptFormat {
+        name: "riviera",
+        description: "Tcl compilation script for Aldec Riviera-PRO",
+        template: Some(include_str!("../script_fmt/aldec_tcl.tera")),
+        default_targets: &["riviera", "simulation"],
+        file_name: "compile.tcl",
+        unsupported: None,
+        options: &[
+            "vcom-arg",
+            "vlog-arg",
+            "vlog-suppress",
+            "vcom-suppress",
+            "vhdl-relaxed",
+            "timescale",
+            "vhdl-lib-map",
+        ],
+    },
+    ScriptFormat {
+        name: "active-hdl",
+        description: "Tcl compilation script for Aldec Active-HDL",
+        template: Some(include_str!("../script_fmt/aldec_tcl.tera")),
+        default_targets: &["active-hdl", "simulation"],
+        file_name: "compile.tcl",
+        unsupported: None,
+        options: &[
+            "vcom-arg",
+            "vlog-arg",
+            "vlog-suppress",
+            "vcom-suppress",
+            "vhdl-relaxed",
+            "timescale",
+            "vhdl-lib-map",
+        ],
+    },
+    ScriptFormat {
+        name: "genus",
+        description: "Tcl compilation script for Cadence Genus",
+        template: Some(include_str!("../script_fmt/genus_tcl.tera")),
+        default_targets: &["genus", "synthesis"],
+        file_name: "compile.tcl",
+        unsupported: None,
+        options: &["elaborate-tops", "top"],
+    },
+    ScriptFormat {
+        name: "jaspergold",
+        description: "Tcl analysis script for Cadence JasperGold",
+        template: Some(include_str!("../script_fmt/jaspergold_tcl.tera")),
+        default_targets: &["jaspergold", "formal", "synthesis"],
+        file_name: "compile.tcl",
+        unsupported: None,
+        options: &["elaborate-tops", "top"],
+    },
+    ScriptFormat {
+        name: "ghdl",
+        description: "Shell analysis script for GHDL (VHDL only)",
+        template: Some(include_str!("../script_fmt/ghdl_sh.tera")),
+        default_targets: &["ghdl", "simulation"],
+        file_name: "compile.sh",
+        unsupported: Some(SourceType::Verilog),
+        options: &["vhdl-relaxed"],
+    },
+    ScriptFormat {
+        name: "yosys",
+        description: "Read script for Yosys (Verilog only)",
+        template: Some(include_str!("../script_fmt/yosys.tera")),
+        default_targets: &["yosys", "synthesis"],
+        file_name: "compile.ys",
+        unsupported: Some(SourceType::Vhdl),
+        options: &["read-verilog-arg"],
+    },
+    ScriptFormat {
+        name: "ghdl-yosys",
+        description: "Synthesis script for Yosys with the GHDL plugin",
+        template: Some(include_str!("../script_fmt/ghdl_yosys.tera")),
+        default_targets: &["synthesis"],
+        file_name: "compile.ys",
+        unsupported: None,
+        options: &["read-verilog-arg", "top"],
+    },
+    ScriptFormat {
+        name: "vivado",
+        description: "Tcl file addition script for Xilinx Vivado",
+        template: Some(include_str!("../script_fmt/vivado_tcl.tera")),
+        default_targets: &["vivado", "fpga", "xilinx", "synthesis"],
+        file_name: "compile.tcl",
+        unsupported: None,
+        options: &[
+            "files-per-command",
+            "top",
+            "only-defines",
+            "only-includes",
+            "only-sources",
+            "no-simset",
+        ],
+    },
+    ScriptFormat {
+        name: "vivado-sim",
+        description: "Tcl file addition script for Xilinx Vivado simulation",
+        template: Some(include_str!("../script_fmt/vivado_tcl.tera")),
+        default_targets: &["vivado", "fpga", "xilinx", "simulation"],
+        file_name: "compile.tcl",
+        unsupported: None,
+        options: &[
+            "files-per-command",
+            "top",
+            "only-defines",
+            "only-includes",
+            "only-sources",
+            "no-simset",
+        ],
+    },
+    ScriptFormat {
+        name: "vivado-project",
+        description: "Tcl project creation script for Xilinx Vivado",
+        template: Some(include_str!("../script_fmt/vivado_project_tcl.tera")),
+        default_targets: &["vivado", "fpga", "xilinx", "synthesis"],
+        file_name: "compile.tcl",
+        unsupported: None,
+        options: &[
+            "files-per-command",
+            "part",
+            "project-name",
+            "top",
+            "only-defines",
+            "only-includes",
+            "only-sources",
+            "no-simset",
+        ],
+    },
+    ScriptFormat {
+        name: "precision",
+        description: "Tcl compilation script for Mentor Precision",
+        template: Some(include_str!("../script_fmt/precision_tcl.tera")),
+        default_targets: &["precision", "fpga", "synthesis"],
+        file_name: "compile.tcl",
+        unsupported: None,
+        options: &[],
+    },
+    ScriptFormat {
+        name: "quartus",
+        description: "Tcl assignment script for Intel Quartus",
+        template: Some(include_str!("../script_fmt/quartus_tcl.tera")),
+        default_targets: &["quartus", "fpga", "synthesis"],
+        file_name: "compile.tcl",
+        unsupported: None,
+        options: &[],
+    },
+    ScriptFormat {
+        name: "libero",
+        description: "Tcl script for Microchip Libero SoC",
+        template: Some(include_str!("../script_fmt/libero_tcl.tera")),
+        default_targets: &["libero", "fpga", "synthesis"],
+        file_name: "compile.tcl",
+        unsupported: None,
+        options: &[],
+    },
+    ScriptFormat {
+        name: "diamond",
+        description: "Tcl script for Lattice Diamond",
+        template: Some(include_str!("../script_fmt/diamond_tcl.tera")),
+        default_targets: &["diamond", "fpga", "synthesis"],
+        file_name: "compile.tcl",
+        unsupported: None,
+        options: &[],
+    },
+    ScriptFormat {
+        name: "edam",
+        description: "EDA metadata (EDAM) JSON for Edalize",
+        template: None,
+        default_targets: &["edam"],
+        file_name: "compile.json",
+        unsupported: None,
+        options: &["top"],
+    },
+    ScriptFormat {
+        name: "fusesoc-core",
+        description: "FuseSoC CAPI2 core file",
+        template: None,
+        default_targets: &["fusesoc"],
+        file_name: "compile.core",
+        unsupported: None,
+        options: &["top"],
+    },
+    ScriptFormat {
+        name: "ipxact",
+        description: "IP-XACT 2014 component",
+        template: None,
+        default_targets: &["ipxact"],
+        file_name: "compile.xml",
+        unsupported: None,
+        options: &[],
+    },
+    ScriptFormat {
+        name: "openlane",
+        description: "OpenLane `config.json` source list (Verilog only)",
+        template: None,
+        default_targets: &["openlane", "asic", "synthesis"],
+        file_name: "compile.json",
+        unsupported: Some(SourceType::Vhdl),
+        options: &["top"],
+    },
+    ScriptFormat {
+        name: "template",
+        description: "Custom tera template (see `--template`)",
+        template: None,
+        default_targets: &[],
+        file_name: "compile.txt",
+        unsupported: None,
+        options: FORMAT_OPTIONS,
+    },
+    ScriptFormat {
+        name: "template_json",
+        description: "Context used to render templates, as JSON",
+        template: None,
+        default_targets: &[],
+        file_name: "compile.json",
+        unsupported: None,
+        options: FORMAT_OPTIONS,
+    },
+];
+
+/// Look up a format by name.
+fn script_format(format: &str) -> Option<&'static ScriptFormat> {
+    FORMATS.iter().find(|f| f.name == format)
+}
+
+/// The default targets of a script format. Unknown formats have none.
+pub fn default_targets_for(format: &str) -> Vec<&'static str> {
+    script_format(format)
+        .map(|f| f.default_targets.to_vec())
+        .unwrap_or_default()
+}
+
+/// The name of the file a format's script is written to with `--output-dir`.
+/// Unknown formats use `compile.txt`.
+fn output_file_name(format: &str) -> &'static str {
+    script_format(format).map_or("compile.txt", |f| f.file_name)
+}
+
+/// Whether a format uses a format-specific option.
+fn option_applies(format: &str, option: &str) -> bool {
+    script_format(format).is_some_and(|f| f.options.contains(&option))
+}
+
+/// Check the format-specific options given on the command line against the
+/// requested formats. Each option must be used by at least one of them.
+fn validate_format_options(matches: &ArgMatches, formats: &[&String]) -> Result<()> {
+    for &option in FORMAT_OPTIONS {
+        let given = matches
+            .value_source(option)
+            .is_some_and(|source| source != ValueSource::DefaultValue);
+        if !given || formats.iter().any(|format| option_applies(format, option)) {
+            continue;
+        }
+        let supported: Vec<String> = FORMATS
+            .iter()
+            .filter(|f| f.options.contains(&option))
+            .map(|f| format!("'{}'", f.name))
+            .collect();
+        let (last, rest) = supported.split_last().unwrap();
+        return Err(Error::new(format!(
+            "--{} can only be used for {}{} format!",
+            option,
+            if rest.is_empty() {
+                String::new()
+            } else {
+                rest.join(", ") + " or "
+            },
+            last
+        )));
+    }
+    if matches.contains_id("param")
+        && formats.iter().any(|format| *format == "vsim")
+        && !matches.contains_id("top")
+        && !matches.get_flag("elaborate-tops")
+    {
+        return Err(Error::new(
+            "--param requires --top or --elaborate-tops for 'vsim' format!",
+        ));
+    }
+    Ok(())
+}
+
+/// The file extension of a format's script, as written with `--output-dir`.
+fn format_extension(format: &str) -> &'static str {
+    let name = output_file_name(format);
+    name.rsplit_once('.').map_or(name, |(_, ext)| ext)
+}
+
+/// The names of the files the scripts of several formats are written to
+/// with `--output-dir`.
+///
+/// Formats whose file names collide have the format inserted before the
+/// extension, e.g. `compile_vsim.tcl` and `compile_synopsys.tcl`.
+fn output_file_names(formats: &[&String]) -> Vec<String> {
+    let names: Vec<&str> = formats
+        .iter()
+        .map(|format| output_file_name(format))
+        .collect();
+    names
+        .iter()
+        .zip(formats)
+        .map(|(name, format)| {
+            if names.iter().filter(|other| *other == name).count() > 1 {
+                match name.rsplit_once('.') {
+                    Some((stem, ext)) => format!("{}_{}.{}", stem, format, ext),
+                    None => format!("{}_{}", name, format),
+                }
+            } else {
+                name.to_string()
+            }
+        })
+        .collect()
+}
+
+/// Print the available formats with their default targets, the file name
+/// used with `--output-dir`, and description.
+fn list_formats() -> Result<()> {
+    for format in FORMATS {
+        println!(
+            "{:<16} {:<32} {:<32} {}",
+            format.name,
+            if format.default_targets.is_empty() {
+                String::from("-")
+            } else {
+                format.default_targets.join(",")
+            },
+            format.file_name,
+            format.description
+        );
+    }
+    Ok(())
+}
+
+/// Print the sorted set of target specifications of all source groups,
+/// followed by those of each package.
+///
+/// Groups without a target specification are not listed.
+fn print_targets(srcs: &SourceGroup) -> Result<()> {
+    fn collect(
+        group: &SourceGroup,
+        package: Option<&str>,
+        targets: &mut BTreeMap<String, BTreeSet<String>>,
+    ) {
+        let package = group.package.or(package);
+        if group.target != TargetSpec::Wildcard {
+            targets
+                .entry(package.unwrap_or_default().to_string())
+                .or_default()
+                .insert(group.target.to_string());
+        }
+        for file in &group.files {
+            if let SourceFile::Group(group) = file {
+                collect(group, package, targets);
+            }
+        }
+    }
+
+    let mut targets = BTreeMap::new();
+    collect(srcs, None, &mut targets);
+    for spec in targets.values().flatten().collect::<BTreeSet<_>>() {
+        println!("{}", spec);
+    }
+    for (package, specs) in &targets {
+        println!("\n{}:", package);
+        for spec in specs {
+            println!("    {}", spec);
+        }
+    }
+    Ok(())
+}
+
+/// Collect the dependencies of all packages with sources, and sort the
+/// packages such that each one comes after all of its dependencies.
+///
+/// Among packages without an order between them, the order of the source
+/// groups is kept.
+fn order_report(srcs: &SourceGroup) -> Result<OrderReport> {
+    fn collect(group: &SourceGroup, dependencies: &mut IndexMap<String, Vec<String>>) {
+        if let Some(package) = group.package {
+            let deps = dependencies.entry(package.to_string()).or_default();
+            for dep in &group.dependencies {
+                if !deps.contains(dep) {
+                    deps.push(dep.clone());
+                }
+            }
+        }
+        for file in &group.files {
+            if let SourceFile::Group(group) = file {
+                collect(group, dependencies);
+            }
+        }
+    }
+
+    fn visit<'a>(
+        package: &'a str,
+        dependencies: &'a IndexMap<String, Vec<String>>,
+        visiting: &mut IndexSet<&'a str>,
+        order: &mut IndexSet<&'a str>,
+    ) -> Result<()> {
+        if order.contains(package) {
+            return Ok(());
+        }
+        if !visiting.insert(package) {
+            return Err(Error::new(format!(
+                "Dependency cycle through package `{}`.",
+                package
+            )));
+        }
+        for dep in dependencies.get(package).into_iter().flatten() {
+            visit(dep, dependencies, visiting, order)?;
+        }
+        visiting.shift_remove(package);
+        order.insert(package);
+        Ok(())
+    }
+
+    let mut dependencies = IndexMap::new();
+    collect(srcs, &mut dependencies);
+    let mut order = IndexSet::new();
+    for package in dependencies.keys() {
+        visit(package, &dependencies, &mut IndexSet::new(), &mut order)?;
+    }
+    let order = order.into_iter().map(String::from).collect();
+    Ok(OrderReport {
+        dependencies,
+        order,
+    })
+}
+
+/// Execute the `script` subcommand.
+pub fn run(sess: &Session, matches: &ArgMatches) -> Result<()> {
+    if matches.get_flag("list-formats") {
+        return list_formats();
+    }
+    if matches.get_flag("print-schema") {
+        print!("{}", TEMPLATE_JSON_SCHEMA);
+        return Ok(());
+    }
+
+    let rt = Runtime::new()?;
+    let io = SessionIo::new(sess);
+    let srcs = if matches.get_flag("no-source-cache") {
+        rt.block_on(io.sources())?
+    } else {
+        rt.block_on(io.cached_sources())?
+    };
+
+    if matches.get_flag("print-targets") {
+        return print_targets(&srcs);
+    }
+    if matches.get_flag("order-report-json") {
+        let report = serde_json::to_string_pretty(&order_report(&srcs)?)
+            .map_err(|e| Error::chain("Failed to serialize order report.", e))?;
+        return write_output(matches, &(report + "\n"));
+    }
+
+    let formats: Vec<&String> = matches.get_many::<String>("format").unwrap().collect();
+    let output_dir = matches.get_one::<String>("output-dir");
+    if formats.len() > 1 && output_dir.is_none() {
+        return Err(Error::new(
+            "Multiple formats can only be used with --output-dir!",
+        ));
+    }
+    validate_format_options(matches, &formats)?;
+
+    // Resolve everything needed from the session up front, such that the
+    // formats can be rendered in parallel.
+    let sess = &ScriptSession {
+        root: sess.root,
+        manifest: sess.manifest,
+        tops: if matches.get_flag("elaborate-tops") {
+            package_tops(sess, &rt, &io, &srcs)?
+        } else {
+            IndexMap::new()
+        },
+    };
+
+    if matches.get_flag("split-output-by-language") {
+        if formats.len() > 1 {
+            return Err(Error::new(
+                "--split-output-by-language can only be used with a single format!",
+            ));
+        }
+        let format = formats[0];
+        for language in [SourceType::Verilog, SourceType::Vhdl] {
+            if let Some(rendered) =
+                render_format(sess, matches, format, srcs.clone(), Some(language))?
+            {
+                let name = format!(
+                    "compile_{}.{}",
+                    language.file_type(),
+                    format_extension(format)
+                );
+                write_script(&Path::new(output_dir.unwrap()).join(name), &rendered)?;
+            }
+        }
+        return Ok(());
+    }
+
+    // The sources are resolved once and shared by all formats, which are
+    // rendered in parallel and written in the order given.
+    let rendered: Vec<Result<Option<String>>> = std::thread::scope(|scope| {
+        let handles: Vec<_> = formats
+            .iter()
+            .map(|format| {
+                let srcs = srcs.clone();
+                scope.spawn(move || render_format(sess, matches, format, srcs, None))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect()
+    });
+    let names = output_file_names(&formats);
+    for (rendered, name) in rendered.into_iter().zip(names) {
+        if let Some(rendered) = rendered? {
+            match output_dir {
+                Some(dir) => write_script(&Path::new(dir).join(name), &rendered)?,
+                None => write_output(matches, &rendered)?,
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The parts of the session that scripts are rendered from.
+struct ScriptSession<'a> {
+    /// The path of the root package.
+    root: &'a Path,
+    /// The manifest of the root package.
+    manifest: &'a Manifest,
+    /// The top-level module of each package, as declared in its manifest.
+    /// Only resolved with `--elaborate-tops`.
+    tops: IndexMap<String, Option<String>>,
+}
+
+/// Render the script of a format from the sources of all packages.
+///
+/// With a `split_language`, as used by `--split-output-by-language`, only
+/// the sources of that language are rendered, and `None` is returned if
+/// there are none.
+fn render_format(
+    sess: &ScriptSession,
+    matches: &ArgMatches,
+    format: &str,
+    mut srcs: SourceGroup,
+    split_language: Option<SourceType>,
+) -> Result<Option<String>> {
+    let format_targets = if !matches.get_flag("no-default-target") {
+        default_targets_for(format)
+    } else {
+        vec![]
+    };
+
+    // Filter the sources by target. Excluded targets are never set.
+    let excluded = TargetSet::new(
+        matches
+            .get_many::<String>("exclude-target")
+            .into_iter()
+            .flatten(),
+    );
+    let without_excluded =
+        |targets: &TargetSet| TargetSet::new(targets.iter().filter(|t| !excluded.contains(t)));
+    let targets = matches
+        .get_many::<String>("target")
         .map(|t| {
             TargetSet::new(
                 t.map(|element| element.as_str())
@@ -245,23 +1576,38 @@ pub fn run(sess: &Session, matches: &ArgMatches) -> Result<()> {
             )
         })
         .unwrap_or_else(|| TargetSet::new(format_targets));
-    srcs = srcs
-        .filter_targets(&targets)
-        .unwrap_or_else(|| SourceGroup {
-            package: Default::default(),
-            independent: true,
-            target: TargetSpec::Wildcard,
-            include_dirs: Default::default(),
-            export_incdirs: Default::default(),
-            defines: Default::default(),
-            files: Default::default(),
-            dependencies: Default::default(),
-            version: None,
-        });
+    let targets = without_excluded(&targets);
+    // A target expression only selects source groups. The targets it sets
+    // to match a group are not active, so they yield no `TARGET_*` defines.
+    let filtered = match matches.get_one::<String>("target-expr") {
+        Some(expr) => {
+            let target_sets: Vec<_> = TargetSpec::from_expr(expr)?
+                .satisfying_sets(&targets)?
+                .iter()
+                .map(without_excluded)
+                .collect();
+            srcs.filter_targets_any(&target_sets)
+        }
+        None => srcs.filter_targets(&targets),
+    };
+    let filtered = filtered.and_then(|srcs| srcs.exclude_targets(&excluded));
+    srcs = filtered.unwrap_or_else(|| SourceGroup {
+        package: Default::default(),
+        independent: true,
+        target: TargetSpec::Wildcard,
+        include_dirs: Default::default(),
+        export_incdirs: Default::default(),
+        defines: Default::default(),
+        vhdl_version: None,
+        sv_version: None,
+        files: Default::default(),
+        dependencies: Default::default(),
+        version: None,
+    });
 
     // Filter the sources by specified packages.
     let packages = &srcs.get_package_list(
-        sess,
+        &sess.manifest.package.name,
         &matches
             .get_many::<String>("package")
             .map(get_package_strings)
@@ -270,12 +1616,17 @@ pub fn run(sess: &Session, matches: &ArgMatches) -> Result<()> {
             .get_many::<String>("exclude")
             .map(get_package_strings)
             .unwrap_or_default(),
-        matches.get_flag("no_deps"),
+        if matches.get_flag("no_deps") {
+            Some(0)
+        } else {
+            matches.get_one::<usize>("depth").copied()
+        },
     );
 
     if matches.contains_id("package")
         || matches.contains_id("exclude")
         || matches.get_flag("no_deps")
+        || matches.contains_id("depth")
     {
         srcs = srcs
             .filter_packages(packages)
@@ -286,6 +1637,8 @@ pub fn run(sess: &Session, matches: &ArgMatches) -> Result<()> {
                 include_dirs: Default::default(),
                 export_incdirs: Default::default(),
                 defines: Default::default(),
+                vhdl_version: None,
+                sv_version: None,
                 files: Default::default(),
                 dependencies: Default::default(),
                 version: None,
@@ -293,128 +1646,141 @@ pub fn run(sess: &Session, matches: &ArgMatches) -> Result<()> {
     }
 
     // Flatten the sources.
-    let srcs = srcs.flatten();
-
-    // Validate format-specific options.
-    if (matches.contains_id("vcom-arg") || matches.contains_id("vlog-arg"))
-        && format != "vsim"
-        && format != "vcs"
-        && format != "riviera"
-        && format != "template"
-        && format != "template_json"
-    {
-        return Err(Error::new(
-            "vsim/vcs-only options can only be used for 'vcs', 'vsim' or 'riviera' format!",
-        ));
+    let mut srcs = srcs.flatten();
+
+    // Filter the sources by language.
+    let only_type = if matches.get_flag("only-verilog") {
+        Some(SourceType::Verilog)
+    } else if matches.get_flag("only-vhdl") {
+        Some(SourceType::Vhdl)
+    } else {
+        split_language
+    };
+    if let Some(only_type) = only_type {
+        for src in &mut srcs {
+            src.files.retain(|f| source_type(f) == Some(only_type));
+        }
+        srcs.retain(|src| !src.files.is_empty());
     }
-    if (matches.get_flag("only-defines")
-        || matches.get_flag("only-includes")
-        || matches.get_flag("only-sources")
-        || matches.get_flag("no-simset"))
-        && !format.starts_with("vivado")
-        && format != "template"
-        && format != "template_json"
-    {
-        return Err(Error::new(
-            "Vivado-only options can only be used for 'vivado' format!",
-        ));
+    if split_language.is_some() && srcs.is_empty() {
+        return Ok(None);
+    }
+    if matches.get_flag("fail-on-empty") && srcs.iter().all(|src| src.files.is_empty()) {
+        return Err(empty_sources_error(&targets, matches));
+    }
+
+    if let Some(mode) = matches.get_one::<String>("dry-run") {
+        return dry_run_summary(&srcs, mode).map(Some);
     }
 
     // Generate the corresponding output.
-    match format.as_str() {
-        "flist" => emit_template(
-            sess,
-            include_str!("../script_fmt/flist.tera"),
-            matches,
-            targets,
-            srcs,
-        ),
-        "flist-plus" => emit_template(
-            sess,
-            include_str!("../script_fmt/flist-plus.tera"),
-            matches,
-            targets,
-            srcs,
-        ),
-        "vsim" => emit_template(
-            sess,
-            include_str!("../script_fmt/vsim_tcl.tera"),
-            matches,
-            targets,
-            srcs,
-        ),
-        "vcs" => emit_template(
-            sess,
-            include_str!("../script_fmt/vcs_sh.tera"),
-            matches,
-            targets,
-            srcs,
-        ),
-        "verilator" => emit_template(
-            sess,
-            include_str!("../script_fmt/verilator_sh.tera"),
-            matches,
-            targets,
-            srcs,
-        ),
-        "synopsys" => emit_template(
-            sess,
-            include_str!("../script_fmt/synopsys_tcl.tera"),
-            matches,
-            targets,
-            srcs,
-        ),
-        "formality" => emit_template(
-            sess,
-            include_str!("../script_fmt/formality_tcl.tera"),
-            matches,
-            targets,
-            srcs,
-        ),
-        "riviera" => emit_template(
-            sess,
-            include_str!("../script_fmt/riviera_tcl.tera"),
-            matches,
-            targets,
-            srcs,
-        ),
-        "genus" => emit_template(
-            sess,
-            include_str!("../script_fmt/genus_tcl.tera"),
-            matches,
-            targets,
-            srcs,
-        ),
-        "vivado" => emit_template(
-            sess,
-            include_str!("../script_fmt/vivado_tcl.tera"),
-            matches,
-            targets,
-            srcs,
-        ),
-        "vivado-sim" => emit_template(
-            sess,
-            include_str!("../script_fmt/vivado_tcl.tera"),
-            matches,
-            targets,
-            srcs,
-        ),
-        "precision" => emit_template(
-            sess,
-            include_str!("../script_fmt/precision_tcl.tera"),
-            matches,
-            targets,
-            srcs,
-        ),
-        "template" => {
+    let script_format = script_format(format).unwrap();
+    if let Some(unsupported) = script_format.unsupported {
+        warn_dropped_files(&srcs, unsupported, format);
+    }
+    let rendered = match script_format.template {
+        None if format == "template" => {
             let custom_tpl_path = Path::new(matches.get_one::<String>("template").unwrap());
             let custom_tpl_str =
                 &String::from_utf8(fs::read(custom_tpl_path)?).map_err(|e| Error::chain("", e))?;
-            emit_template(sess, custom_tpl_str, matches, targets, srcs)
+            emit_template(sess, format, Some(custom_tpl_str), matches, targets, srcs)
         }
-        "template_json" => emit_template(sess, JSON, matches, targets, srcs),
-        _ => unreachable!(),
+        template => emit_template(sess, format, template, matches, targets, srcs),
+    }?;
+    Ok(Some(rendered))
+}
+
+/// The error of `--fail-on-empty`, listing the active targets and package
+/// filters.
+fn empty_sources_error(targets: &TargetSet, matches: &ArgMatches) -> Error {
+    let targets: Vec<&str> = targets.iter().map(String::as_str).collect();
+    let mut filters = vec![];
+    for (id, flag) in [("package", "--package"), ("exclude", "--exclude")] {
+        for package in matches.get_many::<String>(id).into_iter().flatten() {
+            filters.push(format!("{} {}", flag, package));
+        }
+    }
+    if matches.get_flag("no_deps") {
+        filters.push(String::from("--no-deps"));
+    }
+    if let Some(depth) = matches.get_one::<usize>("depth") {
+        filters.push(format!("--depth {}", depth));
+    }
+    Error::new(format!(
+        "No source files remain after filtering.\n\tactive targets: {}\n\tpackage filters: {}",
+        if targets.is_empty() {
+            String::from("(none)")
+        } else {
+            targets.join(", ")
+        },
+        if filters.is_empty() {
+            String::from("(none)")
+        } else {
+            filters.join(", ")
+        }
+    ))
+}
+
+/// The dependency graph of the packages, as printed by `--order-report-json`.
+#[derive(Serialize)]
+struct OrderReport {
+    /// Direct dependencies of each package.
+    dependencies: IndexMap<String, Vec<String>>,
+    /// All packages, each after its dependencies.
+    order: Vec<String>,
+}
+
+/// A source group as summarized by `--dry-run`.
+#[derive(Serialize)]
+struct DryRunGroup<'a> {
+    package: Option<&'a str>,
+    version: Option<String>,
+    target: String,
+    files: usize,
+    languages: Vec<&'static str>,
+}
+
+/// Summarize the source groups that would be emitted, instead of rendering a
+/// script.
+fn dry_run_summary(srcs: &[SourceGroup], mode: &str) -> Result<String> {
+    let groups: Vec<DryRunGroup> = srcs
+        .iter()
+        .map(|src| DryRunGroup {
+            package: src.package,
+            version: src.version.as_ref().map(|v| v.to_string()),
+            target: src.target.simplify().to_string(),
+            files: src.files.len(),
+            languages: src
+                .files
+                .iter()
+                .filter_map(source_type)
+                .map(SourceType::file_type)
+                .collect::<IndexSet<_>>()
+                .into_iter()
+                .collect(),
+        })
+        .collect();
+    if mode == "json" {
+        return serde_json::to_string_pretty(&groups)
+            .map(|json| json + "\n")
+            .map_err(|e| Error::chain("Failed to serialize dry-run summary.", e));
+    }
+    let mut tw = TabWriter::new(vec![]);
+    writeln!(&mut tw, "PACKAGE\tVERSION\tTARGET\tFILES\tLANGUAGES").unwrap();
+    for group in &groups {
+        writeln!(
+            &mut tw,
+            "{}\t{}\t{}\t{}\t{}",
+            group.package.unwrap_or("-"),
+            group.version.as_deref().unwrap_or("-"),
+            group.target,
+            group.files,
+            group.languages.join(",")
+        )
+        .unwrap();
     }
+    tw.flush().unwrap();
+    Ok(String::from_utf8(tw.into_inner().unwrap()).unwrap())
 }
 
 /// Subdivide the source files in a group.
@@ -422,7 +1788,12 @@ pub fn run(sess: &Session, matches: &ArgMatches) -> Result<()> {
 /// The function `cateogrize` is used to assign a category to each source file.
 /// Files with the same category that appear after each other will be kept in
 /// the same source group. Files with different cateogries are split into
-/// separate groups.
+/// separate groups. Files without a category are dropped, and `consume` is
+/// never called with an empty list of files, so a group without any
+/// categorized file produces no group at all.
+///
+/// Since `src` is a single flattened source group, all resulting groups belong
+/// to the same package; groups of different packages are never merged.
 fn separate_files_in_group<F1, F2, T>(mut src: SourceGroup, categorize: F1, mut consume: F2)
 where
     F1: Fn(&SourceFile) -> Option<T>,
@@ -451,160 +1822,706 @@ where
 enum SourceType {
     Verilog,
     Vhdl,
+    Cpp,
+    C,
 }
 
-fn relativize_path(path: &std::path::Path, root: &std::path::Path) -> String {
-    if path.starts_with(root) {
-        format!(
-            "$ROOT/{}",
-            path.strip_prefix(root).unwrap().to_str().unwrap()
-        )
+impl SourceType {
+    /// The name of the language, as used for `file_type` in templates.
+    fn file_type(self) -> &'static str {
+        match self {
+            SourceType::Verilog => "verilog",
+            SourceType::Vhdl => "vhdl",
+            SourceType::Cpp => "cpp",
+            SourceType::C => "c",
+        }
+    }
+}
+
+/// Whether a file is a Verilog header, i.e. a `.svh` or `.vh` file or a file
+/// whose name matches one of `patterns`.
+fn is_header(path: &Path, patterns: &[glob::Pattern]) -> bool {
+    matches!(
+        path.extension().and_then(std::ffi::OsStr::to_str),
+        Some("svh") | Some("vh")
+    ) || path
+        .file_name()
+        .map(|name| {
+            let name = name.to_string_lossy();
+            patterns.iter().any(|pattern| pattern.matches(&name))
+        })
+        .unwrap_or(false)
+}
+
+/// Whether a format compiles the files it lists, such that headers must not
+/// be passed on their own. File lists and package descriptions keep them.
+fn compiles_files(format: &str) -> bool {
+    !matches!(
+        format,
+        "flist" | "flist-plus" | "edam" | "fusesoc-core" | "ipxact" | "template" | "template_json"
+    )
+}
+
+/// Remove header files from a flattened source group, so that headers are
+/// never compiled on their own. They are found through the include
+/// directories declared in the manifests, and a warning is printed for
+/// headers outside of them.
+fn drop_headers<'ctx>(mut src: SourceGroup<'ctx>, patterns: &[glob::Pattern]) -> SourceGroup<'ctx> {
+    let mut files = vec![];
+    for file in std::mem::take(&mut src.files) {
+        match file {
+            SourceFile::File(path) if is_header(path, patterns) => {
+                let declared = path.parent().is_some_and(|dir| {
+                    src.include_dirs.contains(dir)
+                        || src.export_incdirs.values().flatten().any(|d| *d == dir)
+                });
+                if !declared {
+                    warnln!(
+                        "Header {:?} is not in an include directory of its package and cannot be included.",
+                        path
+                    );
+                }
+            }
+            file => files.push(file),
+        }
+    }
+    src.files = files;
+    src
+}
+
+/// Determine the language of a source file from its extension.
+fn source_type(file: &SourceFile) -> Option<SourceType> {
+    match file {
+        SourceFile::File(p) => match p.extension().and_then(std::ffi::OsStr::to_str) {
+            Some("sv") | Some("v") | Some("vp") => Some(SourceType::Verilog),
+            Some("vhd") | Some("vhdl") => Some(SourceType::Vhdl),
+            Some("cpp") | Some("cc") | Some("cxx") => Some(SourceType::Cpp),
+            Some("c") => Some(SourceType::C),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Warn about files of a language that the selected format cannot handle.
+fn warn_dropped_files(srcs: &[SourceGroup], dropped: SourceType, format: &str) {
+    let num_dropped = srcs
+        .iter()
+        .flat_map(|src| src.files.iter())
+        .filter(|f| source_type(f) == Some(dropped))
+        .count();
+    if num_dropped > 0 {
+        warnln!(
+            "{} {} file(s) are not supported by the `{}` format and were omitted.",
+            num_dropped,
+            match dropped {
+                SourceType::Verilog => "Verilog",
+                SourceType::Vhdl => "VHDL",
+                SourceType::Cpp => "C++",
+                SourceType::C => "C",
+            },
+            format
+        );
+    }
+}
+
+/// Make a path relative to `root`, if it lies within it.
+///
+/// The result never starts with a path separator, regardless of whether
+/// `root` ends with one.
+fn relativize_path(path: &Path, root: &Path) -> String {
+    match path.strip_prefix(root) {
+        Ok(rel) => rel
+            .to_string_lossy()
+            .trim_start_matches(['/', '\\'])
+            .to_string(),
+        Err(_) => path.to_string_lossy().into_owned(),
+    }
+}
+
+/// The path of `root` relative to `dir`, as used for `--relative-to`.
+///
+/// A relative `dir` is resolved against the current working directory.
+fn relative_root(root: &Path, dir: &Path) -> Result<String> {
+    let dir = std::env::current_dir()?.join(dir);
+    let dir = dir.canonicalize().unwrap_or(dir);
+    let rel = pathdiff::diff_paths(root, &dir).ok_or_else(|| {
+        Error::new(format!(
+            "Cannot express package root {:?} relative to {:?}.",
+            root, dir
+        ))
+    })?;
+    Ok(if rel.as_os_str().is_empty() {
+        String::from(".")
+    } else {
+        rel.to_string_lossy().into_owned()
+    })
+}
+
+/// Relativize a path to `root`, placing it under `prefix` if given.
+///
+/// Paths outside of `root` are returned unchanged.
+fn rebase_path(path: &Path, root: &Path, prefix: Option<&str>) -> String {
+    let rel = relativize_path(path, root);
+    match prefix {
+        Some(prefix) if path.starts_with(root) => {
+            Path::new(prefix).join(rel).to_string_lossy().into_owned()
+        }
+        _ => rel,
+    }
+}
+
+/// Quote a shell word if it contains characters special to the shell.
+///
+/// The result can be concatenated with other parts of the same word, e.g. the
+/// value of a `+define+NAME=VALUE` argument.
+fn quote_shell(word: &str) -> String {
+    let is_plain = |c: char| c.is_ascii_alphanumeric() || "_-+=.,/:@%^".contains(c);
+    if !word.is_empty() && word.chars().all(is_plain) {
+        word.to_string()
     } else {
-        path.to_str().unwrap().to_string()
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+/// Escape the characters of a Tcl word that would otherwise be substituted or
+/// split the word, e.g. the value of a `+define+NAME=VALUE` argument.
+fn escape_tcl(word: &str) -> String {
+    let mut escaped = String::with_capacity(word.len());
+    for c in word.chars() {
+        if c.is_whitespace() || "{}[]$\"\\;".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
     }
+    escaped
+}
+
+/// Escape the characters of a Make variable value that would otherwise be
+/// expanded or start a comment.
+fn escape_make(word: &str) -> String {
+    word.replace('$', "$$").replace('#', "\\#")
 }
 
 static HEADER_AUTOGEN: &str = "This script was generated automatically by bender.";
 
-fn add_defines_from_matches(defines: &mut IndexMap<String, Option<String>>, matches: &ArgMatches) {
+/// Version of the `template_json` output, to be incremented on incompatible
+/// changes together with the schema.
+static TEMPLATE_JSON_SCHEMA_VERSION: u32 = 1;
+
+/// JSON schema of the `template_json` output.
+static TEMPLATE_JSON_SCHEMA: &str = include_str!("../script_fmt/template_json.schema.json");
+
+fn parse_define(define: &str) -> (String, Option<String>) {
+    let mut parts = define.splitn(2, '=');
+    let name = parts.next().unwrap().trim(); // split always has at least one element
+    let value = parts.next().map(|v| v.trim().to_string());
+    (name.to_string(), value)
+}
+
+/// Collect the defines passed via `--define-file` and `-D`.
+///
+/// Define files are read in the order given, skipping blank lines and `#`
+/// comments. Defines passed with `-D` override same-named ones from files.
+fn defines_from_matches(matches: &ArgMatches) -> Result<IndexMap<String, Option<String>>> {
+    let mut defines = IndexMap::new();
+    if let Some(files) = matches.get_many::<String>("define-file") {
+        for path in files {
+            let content = fs::read_to_string(path).map_err(|cause| {
+                Error::chain(format!("Failed to read define file {:?}.", path), cause)
+            })?;
+            defines.extend(
+                content
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(parse_define),
+            );
+        }
+    }
     if let Some(d) = matches.get_many::<String>("define") {
-        defines.extend(d.map(|t| {
-            let mut parts = t.splitn(2, '=');
-            let name = parts.next().unwrap().trim(); // split always has at least one element
-            let value = parts.next().map(|v| v.trim().to_string());
-            (name.to_string(), value)
-        }));
+        defines.extend(d.map(|t| parse_define(t)));
     }
+    Ok(defines)
 }
 
-static JSON: &str = "json";
+/// Collect the `--param` parameter overrides, in the order given.
+fn params_from_matches(matches: &ArgMatches) -> Result<Vec<(String, String)>> {
+    matches
+        .get_many::<String>("param")
+        .into_iter()
+        .flatten()
+        .map(|param| match param.split_once('=') {
+            Some((name, value)) if !name.trim().is_empty() => {
+                Ok((name.trim().to_string(), value.trim().to_string()))
+            }
+            _ => Err(Error::new(format!(
+                "Invalid parameter `{}`, expected NAME=VALUE.",
+                param
+            ))),
+        })
+        .collect()
+}
 
-fn emit_template(
+/// Collect the `--vhdl-lib-map` library mappings, keyed by package name.
+fn vhdl_lib_map_from_matches(matches: &ArgMatches) -> Result<IndexMap<String, String>> {
+    matches
+        .get_many::<String>("vhdl-lib-map")
+        .into_iter()
+        .flatten()
+        .map(|mapping| match mapping.split_once('=') {
+            Some((package, library))
+                if !package.trim().is_empty() && !library.trim().is_empty() =>
+            {
+                Ok((package.trim().to_string(), library.trim().to_string()))
+            }
+            _ => Err(Error::new(format!(
+                "Invalid library mapping `{}`, expected PACKAGE=LIB.",
+                mapping
+            ))),
+        })
+        .collect()
+}
+
+/// Collect the `--tool-arg` arguments, keyed by tool name.
+fn tool_args_from_matches(matches: &ArgMatches) -> Result<IndexMap<String, Vec<String>>> {
+    let mut tool_args: IndexMap<String, Vec<String>> = IndexMap::new();
+    for arg in matches.get_many::<String>("tool-arg").into_iter().flatten() {
+        match arg.split_once(':') {
+            Some((tool, value)) if !tool.is_empty() => tool_args
+                .entry(tool.to_string())
+                .or_default()
+                .push(value.to_string()),
+            _ => {
+                return Err(Error::new(format!(
+                    "Invalid tool argument `{}`, expected TOOL:ARG.",
+                    arg
+                )))
+            }
+        }
+    }
+    Ok(tool_args)
+}
+
+/// Remove duplicate paths, keeping the last occurrence of each.
+///
+/// A file that is listed again later in the manifest is thus emitted after
+/// everything listed before its last occurrence.
+fn dedup_keep_last<I: IntoIterator<Item = PathBuf>>(paths: I) -> IndexSet<PathBuf> {
+    let mut paths: Vec<PathBuf> = paths.into_iter().collect();
+    paths.reverse();
+    let mut dedup: IndexSet<PathBuf> = paths.into_iter().collect();
+    dedup.reverse();
+    dedup
+}
+
+/// Order the include directories of all source groups.
+///
+/// Without an `--incdir-order`, they are sorted and deduplicated. Otherwise,
+/// they keep the order of the flattened source groups and are deduplicated
+/// keeping the `first` or `last` occurrence, or not at all for `manifest`.
+fn order_incdirs(mut incdirs: Vec<PathBuf>, order: Option<&String>) -> Vec<PathBuf> {
+    match order.map(String::as_str) {
+        None => {
+            incdirs.sort();
+            incdirs.dedup();
+            incdirs
+        }
+        Some("first") => incdirs
+            .into_iter()
+            .collect::<IndexSet<_>>()
+            .into_iter()
+            .collect(),
+        Some("last") => dedup_keep_last(incdirs).into_iter().collect(),
+        Some("manifest") => incdirs,
+        _ => unreachable!(),
+    }
+}
+
+/// Sort files by their file name, ignoring case.
+///
+/// The sort is stable, so files with the same name keep their relative order.
+fn sort_alphabetically(files: &mut IndexSet<PathBuf>) {
+    files.sort_by_cached_key(|path| {
+        path.file_name()
+            .map(|name| name.to_string_lossy().to_lowercase())
+            .unwrap_or_default()
+    });
+}
+
+/// Move files whose name matches one of `patterns` to the front.
+///
+/// The files keep their relative order otherwise.
+fn packages_first(files: &mut IndexSet<PathBuf>, patterns: &[glob::Pattern]) {
+    files.sort_by_cached_key(|path| {
+        !path
+            .file_name()
+            .map(|name| {
+                let name = name.to_string_lossy();
+                patterns.iter().any(|pattern| pattern.matches(&name))
+            })
+            .unwrap_or(false)
+    });
+}
+
+/// Collect the message numbers passed to a `--*-suppress` argument.
+///
+/// Each occurrence may hold several comma-separated numbers; duplicates are
+/// removed.
+fn suppressed_messages(matches: &ArgMatches, id: &str) -> IndexSet<String> {
+    matches
+        .get_many::<String>(id)
+        .into_iter()
+        .flatten()
+        .flat_map(|msgs| msgs.split(','))
+        .map(str::trim)
+        .filter(|msg| !msg.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Look up the top-level module of each package in its manifest, if any.
+fn package_tops(
     sess: &Session,
-    template: &str,
+    rt: &Runtime,
+    io: &SessionIo,
+    srcs: &SourceGroup,
+) -> Result<IndexMap<String, Option<String>>> {
+    let mut tops = IndexMap::new();
+    for src in srcs.clone().flatten() {
+        let package = match src.package {
+            Some(package) if !tops.contains_key(package) => package,
+            _ => continue,
+        };
+        let manifest = if package == sess.manifest.package.name {
+            Some(sess.manifest)
+        } else {
+            rt.block_on(io.dependency_manifest(sess.dependency_with_name(package)?))?
+        };
+        tops.insert(
+            package.to_string(),
+            manifest.and_then(|m| m.package.top.clone()),
+        );
+    }
+    Ok(tops)
+}
+
+/// Print the resolved compile order of each source group to stderr.
+///
+/// `split` lists the packages whose source groups were split into several
+/// groups by language, together with the number of resulting groups.
+fn manifest_order_report(srcs: &[TplSrcStruct], split: &[(String, usize)]) {
+    eprintln!("Compile order ({} groups):", srcs.len());
+    for (i, src) in srcs.iter().enumerate() {
+        let package = if src.package.is_empty() {
+            "-"
+        } else {
+            src.package.as_str()
+        };
+        eprintln!("  [{}] {} ({})", i, package, src.file_type);
+        for file in &src.files {
+            eprintln!("      {}", file.display());
+        }
+    }
+    for (package, num) in split {
+        eprintln!(
+            "Note: a source group of `{}` was split into {} groups by language.",
+            package, num
+        );
+    }
+}
+
+/// Render a script template for the given sources.
+///
+/// Formats without a `template` are not rendered with Tera, but serialized
+/// from the template context by a builder chosen by the format.
+///
+/// The `all_files`, `all_verilog`, and `all_vhdl` lists used in `common`
+/// compilation mode follow the order of the flattened source groups. Files
+/// listed multiple times are only emitted once, at their last occurrence.
+fn emit_template(
+    sess: &ScriptSession,
+    format: &str,
+    template: Option<&str>,
     matches: &ArgMatches,
     targets: TargetSet,
     srcs: Vec<SourceGroup>,
-) -> Result<()> {
-    let mut tera_obj = Tera::default();
-    let mut tera_context = Context::new();
-    tera_context.insert("HEADER_AUTOGEN", HEADER_AUTOGEN);
-    tera_context.insert("root", sess.root);
-    // tera_context.insert("srcs", &srcs);
-    tera_context.insert("abort_on_error", &!matches.get_flag("no-abort-on-error"));
+) -> Result<String> {
+    let header_patterns = match matches.get_many::<String>("header-glob") {
+        Some(patterns) => patterns
+            .map(|pattern| {
+                glob::Pattern::new(pattern).map_err(|cause| {
+                    Error::chain(format!("Invalid header file pattern `{}`.", pattern), cause)
+                })
+            })
+            .collect::<Result<Vec<_>>>()?,
+        None => vec![],
+    };
+    let srcs: Vec<SourceGroup> = if compiles_files(format) {
+        srcs.into_iter()
+            .map(|src| drop_headers(src, &header_patterns))
+            .collect()
+    } else {
+        srcs
+    };
 
-    let mut target_defines: IndexMap<String, Option<String>> = IndexMap::new();
-    target_defines.extend(
-        targets
-            .iter()
-            .map(|t| (format!("TARGET_{}", t.to_uppercase()), None)),
+    // Format-specific options are ignored by formats that do not use them.
+    let format_flag = |option: &str| option_applies(format, option) && matches.get_flag(option);
+
+    let mut tera_obj = match matches.get_one::<String>("template-dir") {
+        Some(dir) if option_applies(format, "template-dir") && template.is_some() => {
+            if !Path::new(dir).is_dir() {
+                return Err(Error::new(format!(
+                    "Template directory {:?} does not exist.",
+                    dir
+                )));
+            }
+            Tera::new(&format!("{}/**/*.tera", dir.trim_end_matches('/'))).map_err(|cause| {
+                Error::chain(format!("Failed to load templates from {:?}.", dir), cause)
+            })?
+        }
+        _ => Tera::default(),
+    };
+    let path_prefix = match matches.get_one::<String>("relative-to") {
+        Some(dir) => Some(relative_root(sess.root, Path::new(dir))?),
+        None => matches.get_one::<String>("prefix-paths").cloned(),
+    };
+    let root = sess.root.to_path_buf();
+    let prefix = path_prefix.clone();
+    tera_obj.register_filter(
+        "relativize",
+        move |value: &tera::Value, args: &HashMap<String, tera::Value>| {
+            let path = tera::try_get_value!("relativize", "value", String, value);
+            // Templates may place paths under a prefix of their own, e.g. a
+            // variable in their language.
+            let prefix = match args.get("prefix") {
+                Some(prefix) => Some(tera::try_get_value!("relativize", "prefix", String, prefix)),
+                None => prefix.clone(),
+            };
+            Ok(tera::Value::String(rebase_path(
+                Path::new(&path),
+                &root,
+                prefix.as_deref(),
+            )))
+        },
+    );
+    tera_obj.register_filter(
+        "quote",
+        |value: &tera::Value, args: &HashMap<String, tera::Value>| {
+            let value = tera::try_get_value!("quote", "value", String, value);
+            let lang = match args.get("lang") {
+                Some(lang) => tera::try_get_value!("quote", "lang", String, lang),
+                None => "sh".to_string(),
+            };
+            match lang.as_str() {
+                "sh" => Ok(tera::Value::String(quote_shell(&value))),
+                "tcl" => Ok(tera::Value::String(escape_tcl(&value))),
+                "make" => Ok(tera::Value::String(escape_make(&value))),
+                _ => Err(tera::Error::msg(format!(
+                    "Filter `quote` received unknown language `{}`, expected `sh`, `tcl`, or `make`.",
+                    lang
+                ))),
+            }
+        },
     );
+    let no_defines = matches.get_flag("no-defines");
+    let define_prefix = matches
+        .get_one::<String>("define-prefix")
+        .map(String::as_str)
+        .unwrap_or_default();
+    // Target names are case-insensitive and stored in lowercase, so the
+    // uppercase define name of a target is unique.
+    let mut target_defines: IndexMap<String, Option<String>> = IndexMap::new();
+    if !no_defines && !matches.get_flag("no-target-defines") {
+        target_defines.extend(targets.iter().map(|t| {
+            (
+                format!(
+                    "{}TARGET_{}",
+                    define_prefix,
+                    t.to_uppercase().replace('-', "_")
+                ),
+                None,
+            )
+        }));
+    }
     target_defines.sort_keys();
 
+    let cli_defines: IndexMap<String, Option<String>> = defines_from_matches(matches)?
+        .into_iter()
+        .map(|(k, v)| (format!("{}{}", define_prefix, k), v))
+        .collect();
     let mut global_defines = target_defines.clone();
-    add_defines_from_matches(&mut global_defines, matches);
-    tera_context.insert("global_defines", &global_defines);
+    global_defines.extend(cli_defines.clone());
+
+    // Include directories passed on the command line, relative to the root.
+    let cli_incdirs: Vec<PathBuf> = matches
+        .get_many::<String>("incdir")
+        .into_iter()
+        .flatten()
+        .map(|dir| sess.root.join(dir))
+        .collect();
 
     let mut all_defines = IndexMap::new();
     let mut all_incdirs = vec![];
     let mut all_files = vec![];
     let mut all_verilog = vec![];
     let mut all_vhdl = vec![];
+    let mut all_cpp = vec![];
+    let mut all_c = vec![];
     for src in &srcs {
         all_defines.extend(
             src.defines
                 .iter()
-                .map(|(k, &v)| (k.to_string(), v.map(String::from))),
+                .map(|(k, &v)| (format!("{}{}", define_prefix, k), v.map(String::from))),
         );
-        all_incdirs.append(&mut src.clone().get_incdirs());
+        // The private include directories of dependencies are left out,
+        // unless `--no-scope-incdirs` is given.
+        if !matches.get_flag("no-scope-incdirs")
+            && src.package != Some(sess.manifest.package.name.as_str())
+        {
+            all_incdirs.extend(src.export_incdirs.values().flatten().copied());
+        } else {
+            all_incdirs.append(&mut src.clone().get_incdirs());
+        }
         all_files.append(&mut src.files.clone());
     }
     all_defines.extend(target_defines.clone());
-    add_defines_from_matches(&mut all_defines, matches);
-    let all_defines = if (!matches.get_flag("only-includes") && !matches.get_flag("only-sources"))
-        || matches.get_flag("only-defines")
+    all_defines.extend(cli_defines.clone());
+    let all_defines = if !no_defines
+        && ((!format_flag("only-includes") && !format_flag("only-sources"))
+            || format_flag("only-defines"))
     {
         all_defines.into_iter().collect()
     } else {
         IndexSet::new()
     };
-    tera_context.insert("all_defines", &all_defines);
 
-    all_incdirs.sort();
-    let all_incdirs: IndexSet<PathBuf> = if (!matches.get_flag("only-defines")
-        && !matches.get_flag("only-sources"))
-        || matches.get_flag("only-includes")
+    let no_incdirs = matches.get_flag("no-incdirs");
+    let all_incdirs: Vec<PathBuf> = if !no_incdirs
+        && ((!format_flag("only-defines") && !format_flag("only-sources"))
+            || format_flag("only-includes"))
     {
-        all_incdirs.into_iter().map(|p| p.to_path_buf()).collect()
+        let manifest_incdirs = order_incdirs(
+            all_incdirs.into_iter().map(|p| p.to_path_buf()).collect(),
+            matches.get_one::<String>("incdir-order"),
+        );
+        cli_incdirs
+            .iter()
+            .cloned()
+            .chain(
+                manifest_incdirs
+                    .into_iter()
+                    .filter(|dir| !cli_incdirs.contains(dir)),
+            )
+            .collect()
     } else {
-        IndexSet::new()
+        vec![]
     };
-    tera_context.insert("all_incdirs", &all_incdirs);
-    let all_files: IndexSet<PathBuf> = if (!matches.get_flag("only-defines")
-        && !matches.get_flag("only-includes"))
-        || matches.get_flag("only-sources")
+    let mut all_files: IndexSet<PathBuf> = if (!format_flag("only-defines")
+        && !format_flag("only-includes"))
+        || format_flag("only-sources")
     {
-        all_files
-            .into_iter()
-            .filter_map(|file| match file {
-                SourceFile::File(p) => Some(p.to_path_buf()),
-                _ => None,
-            })
-            .collect()
+        dedup_keep_last(all_files.into_iter().filter_map(|file| match file {
+            SourceFile::File(p) => Some(p.to_path_buf()),
+            _ => None,
+        }))
     } else {
         IndexSet::new()
     };
-    tera_context.insert("all_files", &all_files);
 
+    // Dependencies whose sources are not part of the emitted files.
+    let dependencies: IndexSet<String> = srcs
+        .iter()
+        .flat_map(|src| src.dependencies.iter())
+        .filter(|dep| !srcs.iter().any(|src| src.package == Some(dep.as_str())))
+        .map(|dep| dep.to_string())
+        .collect();
+
+    let vcom_version = matches.get_one::<String>("vcom-version");
+    let vlog_version = matches.get_one::<String>("vlog-version");
+    let library = matches.get_one::<String>("library");
+    // The library a package is compiled into, if not `--library` or `work`.
+    // Explicit mappings only apply to VHDL sources.
+    let vhdl_lib_map = if option_applies(format, "vhdl-lib-map") {
+        vhdl_lib_map_from_matches(matches)?
+    } else {
+        IndexMap::new()
+    };
+    let package_library = |package: &str, ty: SourceType| {
+        vhdl_lib_map
+            .get(package)
+            .filter(|_| ty == SourceType::Vhdl)
+            .cloned()
+            .or_else(|| {
+                Some(format!("lib_{}", package)).filter(|_| matches.get_flag("library-per-package"))
+            })
+    };
     let mut split_srcs = vec![];
+    let mut split_groups = vec![];
     for src in srcs {
-        separate_files_in_group(
-            src,
-            |f| match f {
-                SourceFile::File(p) => match p.extension().and_then(std::ffi::OsStr::to_str) {
-                    Some("sv") | Some("v") | Some("vp") => Some(SourceType::Verilog),
-                    Some("vhd") | Some("vhdl") => Some(SourceType::Vhdl),
-                    _ => None,
+        let package = src.package.unwrap_or_default().to_string();
+        let num_before = split_srcs.len();
+        separate_files_in_group(src, source_type, |src, ty, files| {
+            split_srcs.push(TplSrcStruct {
+                defines: if no_defines {
+                    IndexSet::new()
+                } else {
+                    let mut local_defines = IndexMap::new();
+                    local_defines.extend(
+                        src.defines.iter().map(|(k, &v)| {
+                            (format!("{}{}", define_prefix, k), v.map(String::from))
+                        }),
+                    );
+                    local_defines.extend(target_defines.clone());
+                    local_defines.extend(cli_defines.clone());
+                    local_defines.into_iter().collect()
                 },
-                _ => None,
-            },
-            |src, ty, files| {
-                split_srcs.push(TplSrcStruct {
-                    defines: {
-                        let mut local_defines = IndexMap::new();
-                        local_defines.extend(
-                            src.defines
-                                .iter()
-                                .map(|(k, &v)| (k.to_string(), v.map(String::from))),
-                        );
-                        local_defines.extend(target_defines.clone());
-                        add_defines_from_matches(&mut local_defines, matches);
-                        local_defines.into_iter().collect()
-                    },
-                    incdirs: {
-                        let mut incdirs = src
-                            .clone()
-                            .get_incdirs()
-                            .iter()
-                            .map(|p| p.to_path_buf())
-                            .collect::<IndexSet<_>>();
-                        incdirs.sort();
-                        incdirs
-                    },
-                    files: files
+                incdirs: if no_incdirs {
+                    IndexSet::new()
+                } else {
+                    let mut incdirs = src
+                        .clone()
+                        .get_incdirs()
                         .iter()
-                        .map(|f| match f {
-                            SourceFile::File(p) => p.to_path_buf(),
-                            SourceFile::Group(_) => unreachable!(),
-                        })
-                        .collect(),
-                    file_type: match ty {
-                        SourceType::Verilog => "verilog".to_string(),
-                        SourceType::Vhdl => "vhdl".to_string(),
-                    },
-                });
-            },
-        );
+                        .map(|p| p.to_path_buf())
+                        .collect::<IndexSet<_>>();
+                    incdirs.sort();
+                    cli_incdirs.iter().cloned().chain(incdirs).collect()
+                },
+                files: files
+                    .iter()
+                    .map(|f| match f {
+                        SourceFile::File(p) => p.to_path_buf(),
+                        SourceFile::Group(_) => unreachable!(),
+                    })
+                    .collect(),
+                file_type: ty.file_type().to_string(),
+                package: src.package.unwrap_or_default().to_string(),
+                version: src.version.as_ref().map(|v| v.to_string()),
+                vhdl_version: src
+                    .vhdl_version
+                    .map(String::from)
+                    .or_else(|| vcom_version.cloned()),
+                sv_version: src
+                    .sv_version
+                    .map(String::from)
+                    .or_else(|| vlog_version.cloned()),
+                library: src
+                    .package
+                    .and_then(|pkg| package_library(pkg, ty))
+                    .or_else(|| library.cloned()),
+                dependency_libraries: src
+                    .dependencies
+                    .iter()
+                    .filter_map(|dep| package_library(dep, ty))
+                    .collect::<IndexSet<_>>()
+                    .into_iter()
+                    .collect(),
+            });
+        });
+        if split_srcs.len() - num_before > 1 {
+            split_groups.push((package, split_srcs.len() - num_before));
+        }
     }
     for src in &split_srcs {
         match src.file_type.as_str() {
@@ -614,79 +2531,1771 @@ fn emit_template(
             "vhdl" => {
                 all_vhdl.append(&mut src.files.clone().into_iter().collect());
             }
+            "cpp" => {
+                all_cpp.append(&mut src.files.clone().into_iter().collect());
+            }
+            "c" => {
+                all_c.append(&mut src.files.clone().into_iter().collect());
+            }
             _ => {}
         }
     }
-    let split_srcs = if !matches.get_flag("only-defines") && !matches.get_flag("only-includes") {
+    // C/C++ groups are only passed on to formats that can handle them.
+    if !matches!(
+        format,
+        "flist" | "verilator-config" | "verilator-make" | "template" | "template_json"
+    ) {
+        split_srcs.retain(|src| src.file_type == "verilog" || src.file_type == "vhdl");
+    }
+    let libraries: IndexSet<String> =
+        if matches.get_one::<String>("compilation_mode").unwrap() == "common" {
+            library.into_iter().cloned().collect()
+        } else {
+            split_srcs
+                .iter()
+                .filter_map(|src| src.library.clone())
+                .collect()
+        };
+
+    let mut split_srcs = if !format_flag("only-defines") && !format_flag("only-includes") {
         split_srcs
     } else {
         vec![]
     };
-    tera_context.insert("srcs", &split_srcs);
 
-    let all_verilog: IndexSet<PathBuf> =
-        if !matches.get_flag("only-defines") && !matches.get_flag("only-includes") {
-            all_verilog.into_iter().collect()
+    let mut all_verilog: IndexSet<PathBuf> =
+        if !format_flag("only-defines") && !format_flag("only-includes") {
+            dedup_keep_last(all_verilog)
+        } else {
+            IndexSet::new()
+        };
+    let mut all_vhdl: IndexSet<PathBuf> =
+        if !format_flag("only-defines") && !format_flag("only-includes") {
+            dedup_keep_last(all_vhdl)
         } else {
             IndexSet::new()
         };
-    let all_vhdl: IndexSet<PathBuf> =
-        if !matches.get_flag("only-defines") && !matches.get_flag("only-includes") {
-            all_vhdl.into_iter().collect()
+    let mut all_cpp: IndexSet<PathBuf> =
+        if !format_flag("only-defines") && !format_flag("only-includes") {
+            dedup_keep_last(all_cpp)
+        } else {
+            IndexSet::new()
+        };
+    let mut all_c: IndexSet<PathBuf> =
+        if !format_flag("only-defines") && !format_flag("only-includes") {
+            dedup_keep_last(all_c)
         } else {
             IndexSet::new()
         };
-    tera_context.insert("all_verilog", &all_verilog);
-    tera_context.insert("all_vhdl", &all_vhdl);
 
     let vlog_args: Vec<String> = if let Some(args) = matches.get_many::<String>("vlog-arg") {
         args.map(Into::into).collect()
     } else {
         [].to_vec()
     };
-    tera_context.insert("vlog_args", &vlog_args);
+    let vlog_suppress = suppressed_messages(matches, "vlog-suppress");
+    let vcom_suppress = suppressed_messages(matches, "vcom-suppress");
     let vcom_args: Vec<String> = if let Some(args) = matches.get_many::<String>("vcom-arg") {
         args.map(Into::into).collect()
     } else {
         [].to_vec()
     };
-    tera_context.insert("vcom_args", &vcom_args);
-
-    tera_context.insert("vlogan_bin", &matches.get_one::<String>("vlogan-bin"));
-    tera_context.insert("vhdlan_bin", &matches.get_one::<String>("vhdlan-bin"));
-    tera_context.insert("relativize_path", &matches.get_flag("relative-path"));
-    tera_context.insert(
-        "compilation_mode",
-        &matches.get_one::<String>("compilation_mode"),
-    );
+    let read_verilog_args: Vec<String> =
+        if let Some(args) = matches.get_many::<String>("read-verilog-arg") {
+            args.map(Into::into).collect()
+        } else {
+            [].to_vec()
+        };
+    let tool_args = tool_args_from_matches(matches)?;
+    let params = params_from_matches(matches)?;
+    let verilator_args: Vec<String> =
+        if let Some(args) = matches.get_many::<String>("verilator-arg") {
+            args.map(Into::into).collect()
+        } else {
+            [].to_vec()
+        };
+
+    if matches.get_one::<String>("sort-files").unwrap() == "alphabetical" {
+        for files in [
+            &mut all_files,
+            &mut all_verilog,
+            &mut all_vhdl,
+            &mut all_cpp,
+            &mut all_c,
+        ] {
+            sort_alphabetically(files);
+        }
+        for src in &mut split_srcs {
+            sort_alphabetically(&mut src.files);
+        }
+    }
+
+    if matches.get_flag("sv-pkg-first") {
+        let patterns = matches
+            .get_many::<String>("pkg-file-glob")
+            .unwrap()
+            .map(|pattern| {
+                glob::Pattern::new(pattern).map_err(|cause| {
+                    Error::chain(
+                        format!("Invalid package file pattern `{}`.", pattern),
+                        cause,
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        for files in [&mut all_files, &mut all_verilog] {
+            packages_first(files, &patterns);
+        }
+        for src in split_srcs
+            .iter_mut()
+            .filter(|src| src.file_type == "verilog")
+        {
+            packages_first(&mut src.files, &patterns);
+        }
+    }
+
+    if matches.get_flag("manifest-order-report") {
+        manifest_order_report(&split_srcs, &split_groups);
+    }
+
+    let mut package_tops = IndexMap::new();
+    if format_flag("elaborate-tops") {
+        for package in split_srcs.iter().map(|src| src.package.as_str()) {
+            if package_tops.contains_key(package) {
+                continue;
+            }
+            match sess.tops.get(package).cloned().flatten() {
+                Some(top) => {
+                    package_tops.insert(package.to_string(), top);
+                }
+                None => warnln!(
+                    "Package `{}` has no top-level module in its manifest and is not elaborated.",
+                    package
+                ),
+            }
+        }
+    }
 
-    let vivado_filesets = if matches.get_flag("no-simset") {
+    let abort_behavior = if matches.get_flag("no-abort-on-error") {
+        "none"
+    } else {
+        matches
+            .get_one::<String>("abort-behavior")
+            .unwrap()
+            .as_str()
+    };
+
+    let vivado_filesets = if format_flag("no-simset") {
         vec![""]
     } else {
         vec!["", " -simset"]
     };
 
-    tera_context.insert("vivado_filesets", &vivado_filesets);
+    let mut tpl_context = TplContext {
+        schema_version: TEMPLATE_JSON_SCHEMA_VERSION,
+        header_autogen: HEADER_AUTOGEN,
+        header_detail: if matches.get_flag("gen-timestamp") {
+            format!(
+                "Generated by bender {} at {}.",
+                env!("CARGO_PKG_VERSION"),
+                chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+            )
+        } else {
+            String::new()
+        },
+        comment_prefix: matches
+            .get_one::<String>("comment-style")
+            .map(String::as_str)
+            .unwrap_or("#"),
+        root: sess.root,
+        root_prefix: if let Some(prefix) = &path_prefix {
+            prefix.trim_end_matches(['/', '\\']).to_string()
+        } else if matches.get_flag("relative-path") {
+            "$ROOT".to_string()
+        } else {
+            sess.root.to_string_lossy().into_owned()
+        },
+        group_separator: matches
+            .get_one::<String>("group-separator")
+            .map(String::as_str)
+            .unwrap_or_default(),
+        annotate: matches.get_flag("annotate"),
+        compilation_mode: matches.get_one::<String>("compilation_mode"),
+        abort_on_error: abort_behavior != "none",
+        abort_behavior,
+        preserve_define_case: matches.get_flag("preserve-define-case"),
+        relativize_path: matches.get_flag("relative-path") || path_prefix.is_some(),
+        path_prefix,
+        global_defines,
+        defines_map: all_defines
+            .iter()
+            .map(|(name, value)| {
+                let name = if matches.get_flag("preserve-define-case") {
+                    name.clone()
+                } else {
+                    name.to_uppercase()
+                };
+                (name, value.clone())
+            })
+            .collect(),
+        params,
+        all_defines,
+        all_incdirs,
+        all_files,
+        all_verilog,
+        all_vhdl,
+        all_cpp,
+        all_c,
+        srcs: split_srcs,
+        library,
+        libraries,
+        vhdl_version: vcom_version,
+        sv_version: vlog_version,
+        vlog_args,
+        timescale: matches.get_one::<String>("timescale"),
+        compile_log: matches.get_one::<String>("compile-log"),
+        vcom_args,
+        vlog_suppress,
+        vcom_suppress,
+        vhdl_relaxed: matches.get_flag("vhdl-relaxed"),
+        vlogan_bin: matches.get_one::<String>("vlogan-bin"),
+        vhdlan_bin: matches.get_one::<String>("vhdlan-bin"),
+        xvlog_bin: matches.get_one::<String>("xvlog-bin"),
+        xvhdl_bin: matches.get_one::<String>("xvhdl-bin"),
+        dsim_bin: matches.get_one::<String>("dsim-bin"),
+        aldec: aldec_commands(format),
+        ghdl_std: matches.get_one::<String>("ghdl-std"),
+        top: matches.get_one::<String>("top"),
+        package_tops,
+        part: matches.get_one::<String>("part"),
+        project_name: matches
+            .get_one::<String>("project-name")
+            .map(String::as_str)
+            .unwrap_or(sess.manifest.package.name.as_str()),
+        spyglass_goal: matches.get_one::<String>("spyglass-goal"),
+        lec_side: matches.get_one::<String>("lec-side"),
+        read_verilog_args,
+        verilator_args,
+        tool_args,
+        vivado_filesets,
+        vhdl_incdirs: matches.get_flag("vhdl-incdirs"),
+        files_per_command: matches.get_one::<u64>("files-per-command").copied(),
+        user_context: IndexMap::new(),
+    };
+    let mut tera_context = Context::from_serialize(&tpl_context)
+        .map_err(|e| Error::chain("Failed to build template context.", e))?;
+    tpl_context.user_context = user_context(&tera_context, matches)?;
+    for (key, value) in &tpl_context.user_context {
+        tera_context.insert(key, value);
+    }
 
-    if template == "json" {
-        println!("{:#}", tera_context.into_json());
-        return Ok(());
+    let rendered = match (format, template) {
+        (_, Some(template)) => {
+            let rendered = tera_obj
+                .render_str(template, &tera_context)
+                .map_err(|e| Error::chain("Failed to render template.", e))?;
+            if matches.get_flag("strip-comments") {
+                strip_comments(&rendered, tpl_context.comment_prefix)
+            } else {
+                rendered
+            }
+        }
+        ("template_json", None) => {
+            let json = if matches.get_flag("compact") {
+                serde_json::to_string(&tpl_context)
+            } else {
+                serde_json::to_string_pretty(&tpl_context)
+            };
+            json.map_err(|e| Error::chain("Failed to serialize template context.", e))? + "\n"
+        }
+        ("edam", None) => {
+            let edam = edam_from_context(&tpl_context, &sess.manifest.package.name);
+            serde_json::to_string_pretty(&edam)
+                .map_err(|e| Error::chain("Failed to serialize EDAM description.", e))?
+                + "\n"
+        }
+        ("openlane", None) => {
+            let config = openlane_from_context(&tpl_context, &sess.manifest.package.name);
+            serde_json::to_string_pretty(&config)
+                .map_err(|e| Error::chain("Failed to serialize OpenLane configuration.", e))?
+                + "\n"
+        }
+        ("ipxact", None) => ipxact_from_context(&tpl_context, &sess.manifest.package.name)?,
+        ("fusesoc-core", None) => {
+            let core =
+                fusesoc_core_from_context(&tpl_context, &sess.manifest.package.name, &dependencies);
+            let yaml = serde_yaml::to_string(&core)
+                .map_err(|e| Error::chain("Failed to serialize FuseSoC core description.", e))?;
+            format!("CAPI=2:\n# {}\n{}", HEADER_AUTOGEN, yaml)
+        }
+        (_, None) => unreachable!(),
+    };
+    add_boilerplate(rendered, matches)
+}
+
+/// Insert the contents of the `--prepend-file` and `--append-file` files
+/// before and after a rendered script.
+///
+/// Prepended contents are placed after a shebang in the first line, such
+/// that it stays effective.
+fn add_boilerplate(rendered: String, matches: &ArgMatches) -> Result<String> {
+    let read_all = |id: &str| -> Result<String> {
+        let mut contents = String::new();
+        for path in matches.get_many::<String>(id).into_iter().flatten() {
+            let content = fs::read_to_string(path)
+                .map_err(|cause| Error::chain(format!("Failed to read {:?}.", path), cause))?;
+            contents.push_str(&content);
+            if !contents.ends_with('\n') {
+                contents.push('\n');
+            }
+        }
+        Ok(contents)
+    };
+    let prepend = read_all("prepend-file")?;
+    let append = read_all("append-file")?;
+    if prepend.is_empty() && append.is_empty() {
+        return Ok(rendered);
     }
 
-    print!(
-        "{}",
-        tera_obj
-            .render_str(template, &tera_context)
-            .map_err(|e| { Error::chain("Failed to render template.", e) })?
-    );
+    let (shebang, body) = match rendered.split_once('\n') {
+        Some((first, rest)) if first.starts_with("#!") => (format!("{}\n", first), rest),
+        _ => (String::new(), rendered.as_str()),
+    };
+    let mut script = shebang + &prepend + body;
+    if !append.is_empty() && !script.ends_with('\n') {
+        script.push('\n');
+    }
+    script.push_str(&append);
+    Ok(script)
+}
 
-    Ok(())
+/// Remove comment-only lines and collapse consecutive blank lines.
+///
+/// A shebang in the first line is kept.
+fn strip_comments(script: &str, comment_prefix: &str) -> String {
+    let mut stripped = String::with_capacity(script.len());
+    let mut last_blank = false;
+    for (i, line) in script.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if i == 0 && trimmed.starts_with("#!") {
+            stripped.push_str(line);
+            stripped.push('\n');
+            continue;
+        }
+        if trimmed.starts_with(comment_prefix) {
+            continue;
+        }
+        let blank = trimmed.is_empty();
+        if blank && last_blank {
+            continue;
+        }
+        last_blank = blank;
+        stripped.push_str(line);
+        stripped.push('\n');
+    }
+    stripped
 }
 
-#[derive(Debug, Serialize)]
-struct TplSrcStruct {
-    defines: IndexSet<(String, Option<String>)>,
-    incdirs: IndexSet<PathBuf>,
-    files: IndexSet<PathBuf>,
-    file_type: String,
+/// Look up the version of a package among the emitted source groups.
+fn package_version(tpl_context: &TplContext, name: &str) -> String {
+    tpl_context
+        .srcs
+        .iter()
+        .find(|src| src.package == name)
+        .and_then(|src| src.version.clone())
+        .unwrap_or_default()
+}
+
+/// Map a source file to its IP-XACT file type, as also used by Edalize and
+/// FuseSoC.
+fn edalize_file_type(path: &Path) -> Option<&'static str> {
+    match path.extension().and_then(std::ffi::OsStr::to_str) {
+        Some("sv") | Some("svh") | Some("vp") => Some("systemVerilogSource"),
+        Some("v") | Some("vh") => Some("verilogSource"),
+        Some("vhd") | Some("vhdl") => Some("vhdlSource"),
+        Some("cpp") | Some("cc") | Some("cxx") => Some("cppSource"),
+        Some("c") => Some("cSource"),
+        _ => None,
+    }
+}
+
+/// List the `.svh` and `.vh` headers in an include directory, recursively.
+fn include_headers(incdir: &Path) -> Vec<PathBuf> {
+    WalkDir::new(incdir)
+        .follow_links(true)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            e.file_type().is_file()
+                && matches!(
+                    e.path().extension().and_then(std::ffi::OsStr::to_str),
+                    Some("svh") | Some("vh")
+                )
+        })
+        .map(|e| e.into_path())
+        .collect()
+}
+
+/// The commands of the Aldec simulators, whose formats share a template.
+fn aldec_commands(format: &str) -> Option<AldecCommands> {
+    match format {
+        "riviera" => Some(AldecCommands {
+            tool: "riviera",
+            vlib: "vlib",
+            vmap: "vmap",
+            vlog: "vlog",
+            vcom: "vcom",
+        }),
+        "active-hdl" => Some(AldecCommands {
+            tool: "active-hdl",
+            vlib: "alib",
+            vmap: "amap",
+            vlog: "alog",
+            vcom: "acom",
+        }),
+        _ => None,
+    }
+}
+
+/// Build an Edalize EDAM description from the template context.
+///
+/// Include directories are represented by the `.svh` and `.vh` headers they
+/// contain, marked as include files with the directory as `include_path`.
+fn edam_from_context(tpl_context: &TplContext, name: &str) -> Edam {
+    let prefix = tpl_context.path_prefix.as_deref();
+    let emit_path = |path: &Path| {
+        if tpl_context.relativize_path {
+            rebase_path(path, tpl_context.root, prefix)
+        } else {
+            path.to_string_lossy().into_owned()
+        }
+    };
+
+    let mut files = vec![];
+    for incdir in &tpl_context.all_incdirs {
+        for header in include_headers(incdir) {
+            files.push(EdamFile {
+                name: emit_path(&header),
+                file_type: edalize_file_type(&header).unwrap(),
+                is_include_file: true,
+                include_path: Some(emit_path(incdir)),
+            });
+        }
+    }
+    files.extend(tpl_context.all_files.iter().filter_map(|file| {
+        Some(EdamFile {
+            name: emit_path(file),
+            file_type: edalize_file_type(file)?,
+            is_include_file: false,
+            include_path: None,
+        })
+    }));
+
+    let parameters = tpl_context
+        .all_defines
+        .iter()
+        .map(|(define, value)| {
+            let define = if tpl_context.preserve_define_case {
+                define.clone()
+            } else {
+                define.to_uppercase()
+            };
+            let parameter = match value {
+                Some(value) => EdamParameter {
+                    datatype: "str",
+                    default: serde_json::Value::String(value.clone()),
+                    paramtype: "vlogdefine",
+                },
+                None => EdamParameter {
+                    datatype: "bool",
+                    default: serde_json::Value::Bool(true),
+                    paramtype: "vlogdefine",
+                },
+            };
+            (define, parameter)
+        })
+        .collect();
+
+    Edam {
+        name: name.to_string(),
+        files,
+        parameters,
+        toplevel: tpl_context.top.cloned(),
+        tool_options: IndexMap::new(),
+    }
+}
+
+/// Build the source list of an OpenLane `config.json` from the template
+/// context.
+///
+/// With `--relative-path`, paths within the package root use OpenLane's
+/// `dir::` prefix, which resolves them against the directory of the
+/// configuration file.
+fn openlane_from_context(tpl_context: &TplContext, name: &str) -> OpenLaneConfig {
+    let prefix = tpl_context.path_prefix.as_deref();
+    let emit_path = |path: &Path| {
+        if !tpl_context.relativize_path {
+            path.to_string_lossy().into_owned()
+        } else if prefix.is_none() && path.starts_with(tpl_context.root) {
+            format!("dir::{}", relativize_path(path, tpl_context.root))
+        } else {
+            rebase_path(path, tpl_context.root, prefix)
+        }
+    };
+
+    OpenLaneConfig {
+        design_name: tpl_context.top.cloned().unwrap_or_else(|| name.to_string()),
+        verilog_files: tpl_context
+            .all_verilog
+            .iter()
+            .map(|file| emit_path(file))
+            .collect(),
+        verilog_include_dirs: tpl_context
+            .all_incdirs
+            .iter()
+            .map(|incdir| emit_path(incdir))
+            .collect(),
+        verilog_defines: tpl_context
+            .all_defines
+            .iter()
+            .map(|(define, value)| {
+                let define = if tpl_context.preserve_define_case {
+                    define.clone()
+                } else {
+                    define.to_uppercase()
+                };
+                match value {
+                    Some(value) => format!("{}={}", define, value),
+                    None => define,
+                }
+            })
+            .collect(),
+    }
+}
+
+/// Build a FuseSoC CAPI2 core description from the template context.
+///
+/// All files go into a single `rtl` fileset, with paths relative to the
+/// package root, where the `.core` file is expected to be placed.
+fn fusesoc_core_from_context(
+    tpl_context: &TplContext,
+    name: &str,
+    dependencies: &IndexSet<String>,
+) -> FuseSoCCore {
+    let prefix = tpl_context.path_prefix.as_deref();
+    let emit_path = |path: &Path| rebase_path(path, tpl_context.root, prefix);
+    let version = package_version(tpl_context, name);
+
+    let mut files = vec![];
+    for incdir in &tpl_context.all_incdirs {
+        for header in include_headers(incdir) {
+            files.push(IndexMap::from([(
+                emit_path(&header),
+                FuseSoCCoreFile {
+                    file_type: edalize_file_type(&header).unwrap(),
+                    is_include_file: Some(true),
+                    include_path: Some(emit_path(incdir)),
+                },
+            )]));
+        }
+    }
+    files.extend(tpl_context.all_files.iter().filter_map(|file| {
+        Some(IndexMap::from([(
+            emit_path(file),
+            FuseSoCCoreFile {
+                file_type: edalize_file_type(file)?,
+                is_include_file: None,
+                include_path: None,
+            },
+        )]))
+    }));
+
+    FuseSoCCore {
+        name: format!("::{}:{}", name, version),
+        filesets: IndexMap::from([(
+            "rtl".to_string(),
+            FuseSoCCoreFileSet {
+                files,
+                depend: dependencies
+                    .iter()
+                    .map(|dep| format!("::{}:", dep))
+                    .collect(),
+            },
+        )]),
+        targets: IndexMap::from([(
+            "default".to_string(),
+            FuseSoCCoreTarget {
+                filesets: vec!["rtl".to_string()],
+                toplevel: tpl_context.top.cloned(),
+            },
+        )]),
+    }
+}
+
+/// Render an IP-XACT 2014 component with a single `rtl` file set.
+///
+/// Paths are relative to the package root. Include directories are listed as
+/// `dependency` entries of the file set, and the headers they contain as
+/// include files.
+fn ipxact_from_context(tpl_context: &TplContext, name: &str) -> Result<String> {
+    let prefix = tpl_context.path_prefix.as_deref();
+    let emit_path = |path: &Path| rebase_path(path, tpl_context.root, prefix);
+    let version = package_version(tpl_context, name);
+
+    let mut files = vec![];
+    for incdir in &tpl_context.all_incdirs {
+        for header in include_headers(incdir) {
+            files.push((
+                emit_path(&header),
+                edalize_file_type(&header).unwrap(),
+                true,
+            ));
+        }
+    }
+    files.extend(
+        tpl_context
+            .all_files
+            .iter()
+            .filter_map(|file| Some((emit_path(file), edalize_file_type(file)?, false))),
+    );
+
+    let dependencies: Vec<String> = tpl_context
+        .all_incdirs
+        .iter()
+        .map(|incdir| emit_path(incdir))
+        .collect();
+
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    writer
+        .write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))
+        .and_then(|_| {
+            writer
+                .create_element("ipxact:component")
+                .with_attribute((
+                    "xmlns:ipxact",
+                    "http://www.accellera.org/XMLSchema/IPXACT/1685-2014",
+                ))
+                .write_inner_content(|w| {
+                    for (tag, text) in [
+                        ("ipxact:vendor", ""),
+                        ("ipxact:library", ""),
+                        ("ipxact:name", name),
+                        ("ipxact:version", version.as_str()),
+                    ] {
+                        w.create_element(tag)
+                            .write_text_content(BytesText::new(text))?;
+                    }
+                    w.create_element("ipxact:fileSets")
+                        .write_inner_content(|w| {
+                            w.create_element("ipxact:fileSet")
+                                .write_inner_content(|w| {
+                                    write_ipxact_file_set(w, &files, &dependencies)
+                                })?;
+                            Ok(())
+                        })?;
+                    Ok(())
+                })?;
+            Ok(())
+        })
+        .map_err(|e| Error::chain("Failed to serialize IP-XACT description.", e))?;
+    let xml = String::from_utf8(writer.into_inner()).map_err(|e| Error::chain("", e))?;
+    Ok(xml + "\n")
+}
+
+/// Write the contents of the IP-XACT `rtl` file set.
+fn write_ipxact_file_set(
+    w: &mut Writer<Vec<u8>>,
+    files: &[(String, &str, bool)],
+    dependencies: &[String],
+) -> std::io::Result<()> {
+    w.create_element("ipxact:name")
+        .write_text_content(BytesText::new("rtl"))?;
+    for (path, file_type, is_include_file) in files {
+        w.create_element("ipxact:file").write_inner_content(|w| {
+            w.create_element("ipxact:name")
+                .write_text_content(BytesText::new(path))?;
+            w.create_element("ipxact:fileType")
+                .write_text_content(BytesText::new(file_type))?;
+            w.create_element("ipxact:isIncludeFile")
+                .write_text_content(BytesText::new(&is_include_file.to_string()))?;
+            Ok(())
+        })?;
+    }
+    for dependency in dependencies {
+        w.create_element("ipxact:dependency")
+            .write_text_content(BytesText::new(dependency))?;
+    }
+    Ok(())
+}
+
+/// Collect the `--context` and `--context-json` variables for the template.
+///
+/// Variables must not collide with any variable already in `tera_context`.
+fn user_context(
+    tera_context: &Context,
+    matches: &ArgMatches,
+) -> Result<IndexMap<String, serde_json::Value>> {
+    let split = |arg: &str| -> Result<(String, String)> {
+        match arg.split_once('=') {
+            Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+            _ => Err(Error::new(format!(
+                "Invalid context variable `{}`, expected KEY=VALUE.",
+                arg
+            ))),
+        }
+    };
+    let mut user_context = IndexMap::new();
+    for arg in matches.get_many::<String>("context").into_iter().flatten() {
+        let (key, value) = split(arg)?;
+        user_context.insert(key, serde_json::Value::String(value));
+    }
+    for arg in matches
+        .get_many::<String>("context-json")
+        .into_iter()
+        .flatten()
+    {
+        let (key, value) = split(arg)?;
+        let value = serde_json::from_str(&value).map_err(|cause| {
+            Error::chain(
+                format!("Failed to parse JSON value of context variable `{}`.", key),
+                cause,
+            )
+        })?;
+        user_context.insert(key, value);
+    }
+    if let Some(key) = user_context
+        .keys()
+        .find(|key| tera_context.contains_key(key))
+    {
+        return Err(Error::new(format!(
+            "Context variable `{}` collides with a reserved template variable.",
+            key
+        )));
+    }
+    Ok(user_context)
+}
+
+/// Write the generated script to the `--output` file, or to stdout if absent.
+fn write_output(matches: &ArgMatches, content: &str) -> Result<()> {
+    match matches.get_one::<String>("output") {
+        Some(path) => write_script(Path::new(path), content),
+        None => {
+            print!("{}", content);
+            Ok(())
+        }
+    }
+}
+
+/// Write a generated script to a file, creating its directory if needed.
+fn write_script(path: &Path, content: &str) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).map_err(|cause| {
+            Error::chain(format!("Failed to create directory {:?}", parent), cause)
+        })?;
+    }
+    fs::write(path, content)
+        .map_err(|cause| Error::chain(format!("Failed to write script to {:?}", path), cause))
+}
+
+/// The variables available to script templates.
+///
+/// This is also the schema of the `template_json` output, with keys in the
+/// order of the fields below. Changes must be reflected in
+/// `template_json.schema.json`.
+#[derive(Debug, Serialize)]
+struct TplContext<'a> {
+    /// Version of the `template_json` schema, see `--print-schema`.
+    schema_version: u32,
+    /// Comment noting that the script was generated by bender.
+    #[serde(rename = "HEADER_AUTOGEN")]
+    header_autogen: &'a str,
+    /// Bender version and generation time with `--gen-timestamp`, otherwise
+    /// empty to keep the script reproducible.
+    #[serde(rename = "HEADER_DETAIL")]
+    header_detail: String,
+    /// Prefix of comment lines, `#` unless set with `--comment-style`.
+    comment_prefix: &'a str,
+    /// Root directory of the package.
+    root: &'a Path,
+    /// Replacement for `root` in emitted paths: the `--prefix-paths` directory,
+    /// `$ROOT` with `--relative-path`, or otherwise `root` itself.
+    root_prefix: String,
+    /// Directory that replaces `root` in relativized paths, from
+    /// `--prefix-paths` or `--relative-to`.
+    #[serde(skip)]
+    path_prefix: Option<String>,
+    /// Line inserted between source groups in `separate` compilation mode,
+    /// empty by default.
+    group_separator: &'a str,
+    /// Whether to annotate the file list with the package, defines, and
+    /// include directories of each group, with `--annotate`.
+    annotate: bool,
+    /// Either `separate` or `common`.
+    compilation_mode: Option<&'a String>,
+    abort_on_error: bool,
+    /// Tcl command used to abort on the first caught error, either `return` or
+    /// `exit`; `none` if `abort_on_error` is not set.
+    abort_behavior: &'a str,
+    preserve_define_case: bool,
+    relativize_path: bool,
+    /// Defines passed on the command line and derived from the targets.
+    ///
+    /// The `TARGET_*` defines come first, sorted by name, followed by the
+    /// command line defines in the order given.
+    global_defines: IndexMap<String, Option<String>>,
+    /// Defines of all groups in manifest order, followed by `global_defines`.
+    all_defines: IndexSet<(String, Option<String>)>,
+    /// `all_defines` keyed by name, uppercase unless `preserve_define_case`
+    /// is set, to look up defines in templates.
+    defines_map: IndexMap<String, Option<String>>,
+    /// Top-level parameter overrides given with `--param`, as name and value.
+    params: Vec<(String, String)>,
+    /// Include directories of all groups, see `--incdir-order`.
+    all_incdirs: Vec<PathBuf>,
+    all_files: IndexSet<PathBuf>,
+    all_verilog: IndexSet<PathBuf>,
+    all_vhdl: IndexSet<PathBuf>,
+    /// C++ sources (`.cpp`, `.cc`, `.cxx`), e.g. for DPI.
+    all_cpp: IndexSet<PathBuf>,
+    /// C sources (`.c`), e.g. for DPI.
+    all_c: IndexSet<PathBuf>,
+    /// Source groups, split such that each only contains files of one language.
+    srcs: Vec<TplSrcStruct>,
+    library: Option<&'a String>,
+    libraries: IndexSet<String>,
+    vhdl_version: Option<&'a String>,
+    sv_version: Option<&'a String>,
+    vlog_args: Vec<String>,
+    vcom_args: Vec<String>,
+    /// Message numbers to suppress in `vlog` and `vcom` calls.
+    vlog_suppress: IndexSet<String>,
+    vcom_suppress: IndexSet<String>,
+    /// Whether VHDL compile calls relax the language rules, with
+    /// `--vhdl-relaxed`.
+    vhdl_relaxed: bool,
+    /// Default timescale of Verilog compile calls.
+    timescale: Option<&'a String>,
+    /// Log file of the compile calls, with `--compile-log`.
+    compile_log: Option<&'a String>,
+    vlogan_bin: Option<&'a String>,
+    vhdlan_bin: Option<&'a String>,
+    xvlog_bin: Option<&'a String>,
+    xvhdl_bin: Option<&'a String>,
+    /// Directory of the DSim analysis commands.
+    dsim_bin: Option<&'a String>,
+    /// Commands of the `riviera` and `active-hdl` formats.
+    aldec: Option<AldecCommands>,
+    ghdl_std: Option<&'a String>,
+    /// Top-level module to elaborate.
+    top: Option<&'a String>,
+    /// Top-level module of each package, keyed by package name, with
+    /// `--elaborate-tops`.
+    package_tops: IndexMap<String, String>,
+    /// Xilinx part of the created Vivado project.
+    part: Option<&'a String>,
+    /// Name of the created Vivado project.
+    project_name: &'a str,
+    /// SpyGlass goal to run after reading the design.
+    spyglass_goal: Option<&'a String>,
+    /// Conformal LEC design side, either `golden` or `revised`.
+    lec_side: Option<&'a String>,
+    read_verilog_args: Vec<String>,
+    verilator_args: Vec<String>,
+    /// Arguments passed with `--tool-arg`, keyed by tool name.
+    tool_args: IndexMap<String, Vec<String>>,
+    vivado_filesets: Vec<&'a str>,
+    /// Whether VHDL groups also add their include directories to the search
+    /// path, with `--vhdl-incdirs`.
+    vhdl_incdirs: bool,
+    /// Maximum number of files per `add_files` call, unlimited if not set.
+    files_per_command: Option<u64>,
+    /// Variables passed with `--context` and `--context-json`.
+    #[serde(flatten)]
+    user_context: IndexMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Serialize)]
+struct TplSrcStruct {
+    defines: IndexSet<(String, Option<String>)>,
+    incdirs: IndexSet<PathBuf>,
+    files: IndexSet<PathBuf>,
+    file_type: String,
+    package: String,
+    version: Option<String>,
+    vhdl_version: Option<String>,
+    sv_version: Option<String>,
+    library: Option<String>,
+    dependency_libraries: Vec<String>,
+}
+
+/// The commands used by the shared template of the Aldec simulators.
+#[derive(Debug, Serialize)]
+struct AldecCommands {
+    /// Key of the simulator's `--tool-arg` arguments.
+    tool: &'static str,
+    vlib: &'static str,
+    vmap: &'static str,
+    vlog: &'static str,
+    vcom: &'static str,
+}
+
+/// An Edalize EDAM description, as emitted by the `edam` format.
+#[derive(Debug, Serialize)]
+struct Edam {
+    name: String,
+    files: Vec<EdamFile>,
+    /// Defines, as `vlogdefine` parameters.
+    parameters: IndexMap<String, EdamParameter>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    toplevel: Option<String>,
+    tool_options: IndexMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Serialize)]
+struct EdamFile {
+    name: String,
+    file_type: &'static str,
+    is_include_file: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    include_path: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct EdamParameter {
+    datatype: &'static str,
+    default: serde_json::Value,
+    paramtype: &'static str,
+}
+
+/// The source list of an OpenLane `config.json`, as emitted by the `openlane`
+/// format.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+struct OpenLaneConfig {
+    design_name: String,
+    verilog_files: Vec<String>,
+    verilog_include_dirs: Vec<String>,
+    verilog_defines: Vec<String>,
+}
+
+/// A FuseSoC CAPI2 core description, as emitted by the `fusesoc-core` format.
+#[derive(Debug, Serialize)]
+struct FuseSoCCore {
+    name: String,
+    filesets: IndexMap<String, FuseSoCCoreFileSet>,
+    targets: IndexMap<String, FuseSoCCoreTarget>,
+}
+
+#[derive(Debug, Serialize)]
+struct FuseSoCCoreFileSet {
+    files: Vec<IndexMap<String, FuseSoCCoreFile>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    depend: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct FuseSoCCoreFile {
+    file_type: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    is_include_file: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    include_path: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct FuseSoCCoreTarget {
+    filesets: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    toplevel: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{PartialManifest, Validate};
+
+    /// A source group of a package with the given files.
+    fn group<'a>(package: &'a str, files: &'a [&'a str]) -> SourceGroup<'a> {
+        SourceGroup {
+            package: Some(package),
+            independent: true,
+            target: TargetSpec::Wildcard,
+            include_dirs: IndexSet::new(),
+            export_incdirs: IndexMap::new(),
+            defines: IndexMap::new(),
+            vhdl_version: None,
+            sv_version: None,
+            files: files
+                .iter()
+                .map(|file| SourceFile::File(Path::new(file)))
+                .collect(),
+            dependencies: IndexSet::new(),
+            version: None,
+        }
+    }
+
+    /// Render a script from the source groups of the package `top` in
+    /// `/top`, with the arguments of `bender script`.
+    fn render(args: &[&str], groups: Vec<SourceGroup>) -> String {
+        let matches = new()
+            .try_get_matches_from(std::iter::once("script").chain(args.iter().copied()))
+            .unwrap();
+        let manifest = serde_yaml::from_str::<PartialManifest>("package:\n  name: top\n")
+            .unwrap()
+            .validate()
+            .unwrap();
+        let sess = ScriptSession {
+            root: Path::new("/top"),
+            manifest: &manifest,
+            tops: IndexMap::new(),
+        };
+        let mut srcs = group("top", &[]);
+        srcs.files = groups
+            .into_iter()
+            .map(|group| SourceFile::Group(Box::new(group)))
+            .collect();
+        let format = matches.get_one::<String>("format").unwrap();
+        render_format(&sess, &matches, format, srcs, None)
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn preserve_define_case() {
+        let srcs = || vec![group("top", &["/top/a.sv"])];
+        let rendered = render(
+            &["flist-plus", "-D", "MyMacro=1", "--preserve-define-case"],
+            srcs(),
+        );
+        assert!(rendered.contains("+define+MyMacro=1\n"));
+        assert!(rendered.contains("+define+TARGET_FLIST\n"));
+        let rendered = render(&["flist-plus", "-D", "MyMacro=1"], srcs());
+        assert!(rendered.contains("+define+MYMACRO=1\n"));
+        assert!(!rendered.contains("MyMacro"));
+    }
+
+    #[test]
+    fn quote_shell_words() {
+        assert_eq!(quote_shell("/ip/src/foo.sv"), "/ip/src/foo.sv");
+        assert_eq!(quote_shell("/my ip/foo.sv"), "'/my ip/foo.sv'");
+        assert_eq!(quote_shell("it's"), "'it'\\''s'");
+        assert_eq!(quote_shell("$HOME"), "'$HOME'");
+        assert_eq!(quote_shell(""), "''");
+    }
+
+    #[test]
+    fn escape_tcl_words() {
+        assert_eq!(escape_tcl("/ip/src/foo.sv"), "/ip/src/foo.sv");
+        assert_eq!(escape_tcl("/my ip/foo.sv"), "/my\\ ip/foo.sv");
+        assert_eq!(escape_tcl("$x[1];{y}"), "\\$x\\[1\\]\\;\\{y\\}");
+        assert_eq!(escape_tcl("\"a\\b\""), "\\\"a\\\\b\\\"");
+    }
+
+    #[test]
+    fn quote_paths_with_spaces() {
+        let srcs = || vec![group("top", &["/top/my ip/foo.sv"])];
+        let rendered = render(&["verilator"], srcs());
+        assert!(
+            rendered.contains("\n\"/top/my ip/foo.sv\"\n"),
+            "{}",
+            rendered
+        );
+        let rendered = render(&["precision"], srcs());
+        assert!(rendered.contains("\"/top/my ip/foo.sv\""), "{}", rendered);
+        let rendered = render(&["iverilog"], srcs());
+        assert!(rendered.contains("'/top/my ip/foo.sv'"), "{}", rendered);
+    }
+
+    #[test]
+    fn only_one_language() {
+        let srcs = || vec![group("top", &["/top/a.sv", "/top/b.vhd", "/top/c.v"])];
+        let rendered = render(&["flist", "--only-vhdl"], srcs());
+        assert!(rendered.contains("/top/b.vhd"));
+        assert!(!rendered.contains(".sv") && !rendered.contains(".v\n"));
+        let rendered = render(&["flist", "--only-verilog"], srcs());
+        assert!(rendered.contains("/top/a.sv") && rendered.contains("/top/c.v"));
+        assert!(!rendered.contains(".vhd"));
+    }
+
+    #[test]
+    fn sort_files() {
+        let srcs = || vec![group("top", &["/top/b.sv", "/top/a.sv", "/top/C.sv"])];
+        let files = |rendered: String| -> Vec<String> {
+            rendered
+                .lines()
+                .filter(|line| line.starts_with('/'))
+                .map(String::from)
+                .collect()
+        };
+        assert_eq!(
+            files(render(&["flist"], srcs())),
+            ["/top/b.sv", "/top/a.sv", "/top/C.sv"]
+        );
+        assert_eq!(
+            files(render(&["flist", "--sort-files", "manifest"], srcs())),
+            ["/top/b.sv", "/top/a.sv", "/top/C.sv"]
+        );
+        assert_eq!(
+            files(render(&["flist", "--sort-files", "alphabetical"], srcs())),
+            ["/top/a.sv", "/top/b.sv", "/top/C.sv"]
+        );
+    }
+
+    #[test]
+    fn fusesoc_core_round_trip() {
+        let mut top = group("top", &["/top/src/a.sv", "/top/src/b.v", "/top/src/c.vhd"]);
+        top.version = Some(semver::Version::new(1, 2, 3));
+        top.dependencies.insert(String::from("ext"));
+        let rendered = render(&["fusesoc-core", "--top", "tb"], vec![top]);
+        assert!(rendered.starts_with("CAPI=2:\n"));
+        let core: serde_yaml::Value = serde_yaml::from_str(&rendered).unwrap();
+        assert_eq!(core["name"].as_str(), Some("::top:1.2.3"));
+        let fileset = &core["filesets"]["rtl"];
+        let files: Vec<(&str, &str)> = fileset["files"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .map(|file| {
+                let (path, attrs) = file.as_mapping().unwrap().iter().next().unwrap();
+                (path.as_str().unwrap(), attrs["file_type"].as_str().unwrap())
+            })
+            .collect();
+        assert_eq!(
+            files,
+            [
+                ("src/a.sv", "systemVerilogSource"),
+                ("src/b.v", "verilogSource"),
+                ("src/c.vhd", "vhdlSource")
+            ]
+        );
+        assert_eq!(fileset["depend"][0].as_str(), Some("::ext:"));
+        assert_eq!(
+            core["targets"]["default"]["filesets"][0].as_str(),
+            Some("rtl")
+        );
+        assert_eq!(core["targets"]["default"]["toplevel"].as_str(), Some("tb"));
+    }
+
+    #[test]
+    fn ipxact_well_formed() {
+        let rendered = render(
+            &["ipxact"],
+            vec![group("top", &["/top/src/a.sv", "/top/src/b.vhd"])],
+        );
+        let mut reader = quick_xml::Reader::from_str(&rendered);
+        let mut path = vec![];
+        let mut files = vec![];
+        loop {
+            match reader.read_event().unwrap() {
+                Event::Start(start) => {
+                    path.push(String::from_utf8(start.name().as_ref().to_vec()).unwrap())
+                }
+                Event::End(_) => {
+                    path.pop();
+                }
+                Event::Text(text)
+                    if path.ends_with(&["ipxact:file".into(), "ipxact:name".into()]) =>
+                {
+                    files.push(text.unescape().unwrap().into_owned())
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        assert!(path.is_empty());
+        assert_eq!(files, ["src/a.sv", "src/b.vhd"]);
+        assert_eq!(rendered.matches("<ipxact:file>").count(), 2);
+    }
+
+    #[test]
+    fn incdir_order() {
+        let srcs = || {
+            let mut first = group("top", &["/top/a.sv"]);
+            first.include_dirs = [Path::new("/top/z"), Path::new("/top/y")].into();
+            let mut second = group("top", &["/top/b.sv"]);
+            second.include_dirs = [Path::new("/top/z")].into();
+            vec![first, second]
+        };
+        let incdirs = |args: &[&str]| -> Vec<String> {
+            render(args, srcs())
+                .lines()
+                .filter_map(|line| line.strip_prefix("+incdir+"))
+                .map(String::from)
+                .collect()
+        };
+        assert_eq!(incdirs(&["flist-plus"]), ["/top/y", "/top/z"]);
+        assert_eq!(
+            incdirs(&["flist-plus", "--incdir-order", "first"]),
+            ["/top/z", "/top/y"]
+        );
+        assert_eq!(
+            incdirs(&["flist-plus", "--incdir-order", "last"]),
+            ["/top/y", "/top/z"]
+        );
+        assert_eq!(
+            incdirs(&["flist-plus", "--incdir-order", "manifest"]),
+            ["/top/z", "/top/y", "/top/z"]
+        );
+    }
+
+    #[test]
+    fn dry_run_lists_packages() {
+        let srcs = || {
+            let mut dep = group("dep", &["/dep/a.vhd"]);
+            dep.version = Some(semver::Version::new(0, 1, 0));
+            vec![dep, group("top", &["/top/b.sv", "/top/c.sv"])]
+        };
+        let rendered = render(&["flist", "--dry-run"], srcs());
+        let lines: Vec<Vec<&str>> = rendered
+            .lines()
+            .map(|line| line.split_whitespace().collect())
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                vec!["PACKAGE", "VERSION", "TARGET", "FILES", "LANGUAGES"],
+                vec!["dep", "0.1.0", "*", "1", "vhdl"],
+                vec!["top", "-", "*", "2", "verilog"],
+            ]
+        );
+        let rendered = render(&["flist", "--dry-run", "json"], srcs());
+        let groups: serde_json::Value = serde_json::from_str(&rendered).unwrap();
+        assert_eq!(groups[0]["package"], "dep");
+        assert_eq!(groups[0]["version"], "0.1.0");
+        assert_eq!(groups[1]["package"], "top");
+        assert_eq!(groups[1]["files"], 2);
+        assert_eq!(groups.as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn sv_pkg_first() {
+        let srcs = || {
+            vec![group(
+                "top",
+                &["/top/foo.sv", "/top/bar_pkg.sv", "/top/baz_defs.sv"],
+            )]
+        };
+        let files = |args: &[&str]| -> Vec<String> {
+            render(args, srcs())
+                .lines()
+                .filter(|line| line.starts_with('/'))
+                .map(String::from)
+                .collect()
+        };
+        assert_eq!(
+            files(&["flist"]),
+            ["/top/foo.sv", "/top/bar_pkg.sv", "/top/baz_defs.sv"]
+        );
+        assert_eq!(
+            files(&["flist", "--sv-pkg-first"]),
+            ["/top/bar_pkg.sv", "/top/foo.sv", "/top/baz_defs.sv"]
+        );
+        assert_eq!(
+            files(&["flist", "--sv-pkg-first", "--pkg-file-glob", "*_defs.sv"]),
+            ["/top/baz_defs.sv", "/top/foo.sv", "/top/bar_pkg.sv"]
+        );
+    }
+
+    #[test]
+    fn gen_timestamp() {
+        let srcs = || vec![group("top", &["/top/a.sv"])];
+        let rendered = render(&["vsim"], srcs());
+        assert!(rendered.starts_with(&format!("# {}\n", HEADER_AUTOGEN)));
+        assert!(!rendered.contains("Generated by bender"));
+        let rendered = render(&["vsim", "--gen-timestamp"], srcs());
+        let detail = rendered.lines().nth(1).unwrap();
+        assert!(
+            detail.starts_with(&format!(
+                "# Generated by bender {} at ",
+                env!("CARGO_PKG_VERSION")
+            )),
+            "{}",
+            detail
+        );
+        let timestamp = detail.rsplit(' ').next().unwrap().trim_end_matches('.');
+        assert!(chrono::DateTime::parse_from_rfc3339(timestamp).is_ok());
+        let rendered = render(&["vsim", "--gen-timestamp", "--no-gen-timestamp"], srcs());
+        assert!(!rendered.contains("Generated by bender"));
+    }
+
+    #[test]
+    fn strip_comments_of_scripts() {
+        assert_eq!(
+            strip_comments(
+                "#!/bin/sh\n# banner\n\n\n\nfoo # bar\n  # indented\n\nbaz\n",
+                "#"
+            ),
+            "#!/bin/sh\n\nfoo # bar\n\nbaz\n"
+        );
+        assert_eq!(strip_comments("// a\nb\n", "//"), "b\n");
+
+        let srcs = || vec![group("top", &["/top/a.sv"]), group("top", &["/top/b.sv"])];
+        let rendered = render(&["vcs", "--strip-comments"], srcs());
+        assert!(
+            rendered.starts_with("#!/usr/bin/env bash\n"),
+            "{}",
+            rendered
+        );
+        assert!(!rendered.contains(HEADER_AUTOGEN));
+        assert!(!rendered.contains("\n\n\n"));
+        assert!(rendered.contains("/top/b.sv"));
+        let rendered = render(&["vsim", "--strip-comments"], srcs());
+        assert!(!rendered
+            .lines()
+            .any(|line| line.trim_start().starts_with('#')));
+        assert!(!rendered.contains("\n\n\n"));
+        assert!(rendered.contains("vlog"));
+    }
+
+    #[test]
+    fn files_per_command() {
+        let srcs = || {
+            vec![group(
+                "top",
+                &[
+                    "/top/a.sv",
+                    "/top/b.sv",
+                    "/top/c.sv",
+                    "/top/d.sv",
+                    "/top/e.sv",
+                ],
+            )]
+        };
+        let rendered = render(&["vivado", "--no-simset"], srcs());
+        assert_eq!(rendered.matches("add_files").count(), 1);
+        for format in ["vivado", "vivado-project"] {
+            let rendered = render(&[format, "--no-simset", "--files-per-command", "2"], srcs());
+            let calls: Vec<usize> = rendered
+                .split("add_files")
+                .skip(1)
+                .map(|call| call[..call.find("\n]").unwrap()].matches(".sv").count())
+                .collect();
+            assert_eq!(calls, [2, 2, 1], "{}", rendered);
+        }
+    }
+
+    #[test]
+    fn defines_map() {
+        let defines = |args: &[&str]| -> serde_json::Value {
+            let rendered = render(args, vec![group("top", &["/top/a.sv"])]);
+            serde_json::from_str::<serde_json::Value>(&rendered).unwrap()["defines_map"].clone()
+        };
+        assert_eq!(
+            defines(&["template_json", "-D", "Width=8", "-D", "FLAG"]),
+            serde_json::json!({ "WIDTH": "8", "FLAG": null })
+        );
+        assert_eq!(
+            defines(&["template_json", "-D", "Width=8", "--preserve-define-case"]),
+            serde_json::json!({ "Width": "8" })
+        );
+    }
+
+    #[test]
+    fn template_json_matches_schema() {
+        let schema: serde_json::Value = serde_json::from_str(TEMPLATE_JSON_SCHEMA).unwrap();
+        let validator = jsonschema::validator_for(&schema).unwrap();
+        let srcs = || {
+            let mut dep = group("dep", &["/dep/a.vhd", "/dep/b.sv"]);
+            dep.version = Some(semver::Version::new(1, 0, 0));
+            dep.defines.insert("DEP", Some("1"));
+            dep.include_dirs.insert(Path::new("/dep/include"));
+            let mut top = group("top", &["/top/c.sv", "/top/d.cpp"]);
+            top.dependencies.insert(String::from("dep"));
+            vec![dep, top]
+        };
+        for args in [
+            &["template_json"][..],
+            &[
+                "template_json",
+                "--compact",
+                "-D",
+                "FOO=2",
+                "--param",
+                "W=8",
+            ],
+            &[
+                "template_json",
+                "--relative-path",
+                "--top",
+                "tb",
+                "--library-per-package",
+            ],
+        ] {
+            let output: serde_json::Value = serde_json::from_str(&render(args, srcs())).unwrap();
+            let errors: Vec<String> = validator
+                .iter_errors(&output)
+                .map(|error| format!("{} at {}", error, error.instance_path))
+                .collect();
+            assert!(errors.is_empty(), "{:?}: {:#?}", args, errors);
+        }
+        let mut output: serde_json::Value =
+            serde_json::from_str(&render(&["template_json"], srcs())).unwrap();
+        output.as_object_mut().unwrap().remove("schema_version");
+        assert!(!validator.is_valid(&output));
+    }
+
+    #[test]
+    fn group_separator() {
+        for format in ["vsim", "vcs"] {
+            let rendered = render(
+                &[format, "--group-separator", "# ---- {package} ----"],
+                vec![group("dep", &["/dep/a.sv"]), group("top", &["/top/b.sv"])],
+            );
+            assert_eq!(rendered.matches("# ----").count(), 1, "{}", rendered);
+            let separator = rendered.find("# ---- top ----").unwrap();
+            assert!(rendered.find("/dep/a.sv").unwrap() < separator);
+            assert!(separator < rendered.find("/top/b.sv").unwrap());
+        }
+    }
+
+    #[test]
+    fn openlane_config() {
+        let rendered = render(
+            &["openlane"],
+            vec![group("top", &["/top/a.sv", "/top/b.v", "/top/c.vhd"])],
+        );
+        let config: serde_json::Value = serde_json::from_str(&rendered).unwrap();
+        assert_eq!(config["DESIGN_NAME"], "top");
+        assert_eq!(
+            config["VERILOG_FILES"],
+            serde_json::json!(["/top/a.sv", "/top/b.v"])
+        );
+    }
+
+    #[test]
+    fn genus_common_vhdl_search_path() {
+        let mut vhdl = group("top", &["/top/a.vhd"]);
+        vhdl.include_dirs.insert(Path::new("/top/include"));
+        let rendered = render(&["genus", "--compilation-mode", "common"], vec![vhdl]);
+        let search_path = rendered.find("set_db init_hdl_search_path").unwrap();
+        let read_hdl = rendered.find("read_hdl").unwrap();
+        assert!(search_path < read_hdl, "{}", rendered);
+        assert!(rendered[..search_path].contains("lappend search_path \"/top/include\""));
+        assert!(rendered[read_hdl..].contains("-language vhdl"));
+    }
+
+    #[test]
+    fn synthesis_scripts_whitespace() {
+        for format in ["synopsys", "formality", "genus"] {
+            for mode in ["separate", "common"] {
+                for abort in [None, Some("--no-abort-on-error")] {
+                    for with_vhdl in [false, true] {
+                        let mut verilog = group("dep", &["/dep/a.sv"]);
+                        verilog.include_dirs.insert(Path::new("/dep/include"));
+                        verilog.defines.insert("FOO", Some("1"));
+                        let mut groups = vec![verilog];
+                        if with_vhdl {
+                            groups.push(group("top", &["/top/b.vhd"]));
+                        }
+                        let mut args = vec![format, "--compilation-mode", mode];
+                        args.extend(abort);
+                        let rendered = render(&args, groups);
+                        for line in rendered.lines() {
+                            assert_eq!(line, line.trim_end(), "{:?}:\n{}", args, rendered);
+                        }
+                        assert!(!rendered.contains("\n\n\n"), "{:?}:\n{}", args, rendered);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn param_overrides() {
+        let files = ["/top/top.sv"];
+        let vsim = render(
+            &["vsim", "--top", "top", "--param", "WIDTH=8"],
+            vec![group("top", &files)],
+        );
+        assert!(vsim.contains("vopt -gWIDTH=8 top -o top_opt"), "{}", vsim);
+        let vcs = render(
+            &["vcs-filelist", "--param", "WIDTH=8"],
+            vec![group("top", &files)],
+        );
+        assert!(vcs.lines().any(|line| line == "-pvalue+WIDTH=8"), "{}", vcs);
+        let verilator = render(
+            &["verilator", "--param", "WIDTH=8"],
+            vec![group("top", &files)],
+        );
+        assert!(
+            verilator.lines().any(|line| line == "-GWIDTH=8"),
+            "{}",
+            verilator
+        );
+    }
+
+    #[test]
+    fn aldec_commands() {
+        for (format, commands, other) in [
+            (
+                "riviera",
+                ["vlib", "vmap", "vlog", "vcom"],
+                ["alib", "amap", "alog", "acom"],
+            ),
+            (
+                "active-hdl",
+                ["alib", "amap", "alog", "acom"],
+                ["vlib", "vmap", "vlog", "vcom"],
+            ),
+        ] {
+            let rendered = render(
+                &[format, "--library-per-package"],
+                vec![
+                    group("dep", &["/dep/dep.vhd"]),
+                    group("top", &["/top/top.sv"]),
+                ],
+            );
+            let [lib, map, log, com] = commands;
+            assert!(
+                rendered.contains(&format!("{} work\n", lib)),
+                "{}",
+                rendered
+            );
+            assert!(
+                rendered.contains(&format!("{} lib_dep lib_dep\n", map)),
+                "{}",
+                rendered
+            );
+            assert!(rendered.contains(&format!("{} -sv", log)), "{}", rendered);
+            assert!(rendered.contains(&format!("{} -2008", com)), "{}", rendered);
+            for command in other {
+                assert!(!rendered.contains(&format!("{} ", command)), "{}", rendered);
+            }
+        }
+    }
+
+    #[test]
+    fn no_incdirs() {
+        for format in ["flist-plus", "vsim"] {
+            let groups = || {
+                let mut top = group("top", &["/top/top.sv"]);
+                top.include_dirs.insert(Path::new("/top/include"));
+                vec![top]
+            };
+            let rendered = render(&[format], groups());
+            assert!(rendered.contains("+incdir+"), "{}", rendered);
+            let rendered = render(&[format, "--no-incdirs"], groups());
+            assert!(!rendered.contains("+incdir+"), "{}", rendered);
+            assert!(rendered.contains("/top/top.sv"), "{}", rendered);
+        }
+    }
+
+    #[test]
+    fn no_defines() {
+        let mut top = group("top", &["/top/top.sv"]);
+        top.defines.insert("FOO", Some("1"));
+        let rendered = render(&["vsim", "--no-defines"], vec![top]);
+        assert!(!rendered.contains("+define+"), "{}", rendered);
+        assert!(!rendered.contains("TARGET_"), "{}", rendered);
+        assert!(rendered.contains("/top/top.sv"), "{}", rendered);
+    }
+
+    #[test]
+    fn no_target_defines() {
+        let rendered = render(
+            &["vsim", "--no-target-defines", "-D", "FOO"],
+            vec![group("top", &["/top/top.sv"])],
+        );
+        assert!(rendered.contains("+define+FOO"), "{}", rendered);
+        assert!(!rendered.contains("TARGET_"), "{}", rendered);
+    }
+
+    #[test]
+    fn order_report_diamond() {
+        let with_deps = |package, deps: &[&str]| {
+            let mut group = group(package, &[]);
+            group.dependencies = deps.iter().map(|dep| dep.to_string()).collect();
+            SourceFile::Group(Box::new(group))
+        };
+        let mut srcs = group("top", &[]);
+        srcs.package = None;
+        srcs.files = vec![
+            with_deps("top", &["a", "b"]),
+            with_deps("a", &["leaf"]),
+            with_deps("b", &["leaf"]),
+            with_deps("leaf", &[]),
+        ];
+        let report = order_report(&srcs).unwrap();
+        assert_eq!(report.order, ["leaf", "a", "b", "top"]);
+        assert_eq!(report.dependencies["top"], ["a", "b"]);
+    }
+
+    #[test]
+    fn define_prefix() {
+        let mut top = group("top", &["/top/top.sv"]);
+        top.defines.insert("FOO", Some("1"));
+        let rendered = render(
+            &["flist-plus", "--define-prefix", "MYIP_", "-D", "BAR"],
+            vec![top],
+        );
+        let defines: Vec<&str> = rendered
+            .lines()
+            .filter_map(|line| line.strip_prefix("+define+"))
+            .collect();
+        assert!(defines.contains(&"MYIP_FOO=1"), "{}", rendered);
+        assert!(defines.contains(&"MYIP_BAR"), "{}", rendered);
+        assert!(defines.contains(&"MYIP_TARGET_FLIST"), "{}", rendered);
+        assert!(
+            defines.iter().all(|define| define.starts_with("MYIP_")),
+            "{}",
+            rendered
+        );
+    }
+
+    #[test]
+    fn vhdl_lib_map() {
+        for format in ["vsim", "riviera"] {
+            let rendered = render(
+                &[
+                    format,
+                    "--vhdl-lib-map",
+                    "a=liba",
+                    "--vhdl-lib-map",
+                    "b=libb",
+                ],
+                vec![group("a", &["/a/a.vhd"]), group("b", &["/b/b.vhd"])],
+            );
+            let (a, b) = rendered.split_at(rendered.find("/a/a.vhd").unwrap());
+            assert!(a.contains("-work liba"), "{}", rendered);
+            assert!(!a.contains("-work libb"), "{}", rendered);
+            assert!(b.contains("-work libb"), "{}", rendered);
+        }
+    }
+
+    #[test]
+    fn annotate_flist() {
+        let mut top = group("top", &["/top/top.sv"]);
+        top.defines.insert("FOO", Some("1"));
+        top.include_dirs.insert(Path::new("/top/include"));
+        let rendered = render(&["flist", "--annotate"], vec![top]);
+        let lines: Vec<&str> = rendered.lines().collect();
+        let package = lines
+            .iter()
+            .position(|&line| line == "// package: top")
+            .unwrap();
+        let define = lines
+            .iter()
+            .position(|&line| line == "// +define+FOO=1")
+            .unwrap();
+        let incdir = lines
+            .iter()
+            .position(|&line| line == "// +incdir+/top/include")
+            .unwrap();
+        let file = lines
+            .iter()
+            .position(|&line| line == "/top/top.sv")
+            .unwrap();
+        assert!(
+            package < define && define < incdir && incdir < file,
+            "{}",
+            rendered
+        );
+        let rendered = render(&["flist"], vec![group("top", &["/top/top.sv"])]);
+        assert!(!rendered.contains("//"), "{}", rendered);
+    }
+
+    #[test]
+    fn dependency_libraries() {
+        for format in ["vsim", "riviera"] {
+            let mut top = group("top", &["/top/top.vhd"]);
+            top.dependencies.insert(String::from("dep"));
+            let rendered = render(
+                &[format, "--library-per-package"],
+                vec![group("dep", &["/dep/dep.vhd"]), top],
+            );
+            assert_eq!(rendered.matches("-L lib_dep ").count(), 1, "{}", rendered);
+            assert_eq!(rendered.matches("-L ").count(), 1, "{}", rendered);
+            let top_call = &rendered[rendered.find("-work lib_top").unwrap()..];
+            assert!(top_call.contains("-L lib_dep "), "{}", rendered);
+        }
+    }
+
+    #[test]
+    fn default_targets_of_formats() {
+        assert_eq!(
+            default_targets_for("formality"),
+            ["synopsys", "synthesis", "formality"]
+        );
+        assert_eq!(
+            default_targets_for("vivado"),
+            ["vivado", "fpga", "xilinx", "synthesis"]
+        );
+        assert!(default_targets_for("template").is_empty());
+        assert!(default_targets_for("unknown").is_empty());
+    }
+
+    #[test]
+    fn output_file_names_of_formats() {
+        assert_eq!(output_file_name("vsim"), "compile.tcl");
+        assert_eq!(output_file_name("vcs"), "compile.sh");
+        assert_eq!(output_file_name("verilator"), "compile.sh");
+        assert_eq!(output_file_name("flist"), "sources.f");
+        assert_eq!(output_file_name("flist-plus"), "sources.f");
+        assert_eq!(output_file_name("yosys"), "compile.ys");
+        assert_eq!(format_extension("flist"), "f");
+    }
+
+    #[test]
+    fn output_file_names_with_collisions() {
+        let formats = ["vsim", "vcs", "verilator", "flist", "yosys"].map(String::from);
+        assert_eq!(
+            output_file_names(&formats.iter().collect::<Vec<_>>()),
+            [
+                "compile.tcl",
+                "compile_vcs.sh",
+                "compile_verilator.sh",
+                "sources.f",
+                "compile.ys"
+            ]
+        );
+        let formats = ["flist", "flist-plus"].map(String::from);
+        assert_eq!(
+            output_file_names(&formats.iter().collect::<Vec<_>>()),
+            ["sources_flist.f", "sources_flist-plus.f"]
+        );
+    }
+
+    #[test]
+    fn format_options_are_arguments() {
+        let cmd = new();
+        for &option in FORMAT_OPTIONS {
+            assert!(
+                cmd.get_arguments()
+                    .any(|arg| arg.get_id() == option && arg.get_long() == Some(option)),
+                "format option `{}` is not an argument",
+                option
+            );
+        }
+        for format in FORMATS {
+            for option in format.options {
+                assert!(
+                    FORMAT_OPTIONS.contains(option),
+                    "option `{}` of format `{}` is not a format option",
+                    option,
+                    format.name
+                );
+            }
+        }
+    }
+
+    fn validate(args: &[&str]) -> Result<()> {
+        let matches = new().try_get_matches_from(args).unwrap();
+        let formats: Vec<&String> = matches.get_many::<String>("format").unwrap().collect();
+        validate_format_options(&matches, &formats)
+    }
+
+    #[test]
+    fn format_options_of_several_formats() {
+        assert!(validate(&["script", "vsim", "flist", "--top", "tb"]).is_ok());
+        assert!(validate(&["script", "flist", "vsim", "--annotate"]).is_ok());
+        assert!(validate(&["script", "template_json", "--vhdl-relaxed"]).is_ok());
+        let err = validate(&["script", "flist", "vcs", "--part", "xc7a"]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "--part can only be used for 'vivado-project', 'template' or 'template_json' format!"
+        );
+        assert!(validate(&["script", "vsim", "vcs-filelist", "--param", "W=8"]).is_err());
+        assert!(validate(&["script", "vcs-filelist", "--param", "W=8"]).is_ok());
+    }
 }
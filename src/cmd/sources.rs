@@ -53,6 +53,14 @@ pub fn new() -> Command {
                 .action(ArgAction::SetTrue)
                 .help("Exclude all dependencies, i.e. only top level or specified package(s)"),
         )
+        .arg(
+            Arg::new("depth")
+                .long("depth")
+                .help("Only include dependencies up to the given depth below the top level or specified package(s); 0 is the same as --no-deps")
+                .num_args(1)
+                .conflicts_with("no_deps")
+                .value_parser(value_parser!(usize)),
+        )
         .arg(
             Arg::new("exclude")
                 .short('e')
@@ -109,6 +117,8 @@ pub fn run(sess: &Session, matches: &ArgMatches) -> Result<()> {
             include_dirs: Default::default(),
             export_incdirs: Default::default(),
             defines: Default::default(),
+            vhdl_version: None,
+            sv_version: None,
             files: Default::default(),
             dependencies: Default::default(),
             version: None,
@@ -116,7 +126,7 @@ pub fn run(sess: &Session, matches: &ArgMatches) -> Result<()> {
 
     // Filter the sources by specified packages.
     let packages = &srcs.get_package_list(
-        sess,
+        &sess.manifest.package.name,
         &matches
             .get_many::<String>("package")
             .map(get_package_strings)
@@ -125,12 +135,17 @@ pub fn run(sess: &Session, matches: &ArgMatches) -> Result<()> {
             .get_many::<String>("exclude")
             .map(get_package_strings)
             .unwrap_or_default(),
-        matches.get_flag("no_deps"),
+        if matches.get_flag("no_deps") {
+            Some(0)
+        } else {
+            matches.get_one::<usize>("depth").copied()
+        },
     );
 
     if matches.contains_id("package")
         || matches.contains_id("exclude")
         || matches.get_flag("no_deps")
+        || matches.contains_id("depth")
     {
         srcs = srcs
             .filter_packages(packages)
@@ -141,6 +156,8 @@ pub fn run(sess: &Session, matches: &ArgMatches) -> Result<()> {
                 include_dirs: Default::default(),
                 export_incdirs: Default::default(),
                 defines: Default::default(),
+                vhdl_version: None,
+                sv_version: None,
                 files: Default::default(),
                 dependencies: Default::default(),
                 version: None,
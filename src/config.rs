@@ -81,6 +81,9 @@ pub struct Package {
     /// A list of package authors. Each author should be of the form `John Doe
     /// <john@doe.com>`.
     pub authors: Option<Vec<String>>,
+    /// The top-level module of the package, as elaborated by `bender script
+    /// --elaborate-tops`.
+    pub top: Option<String>,
 }
 
 /// A dependency.
@@ -146,6 +149,10 @@ pub struct Sources {
     pub include_dirs: Vec<PathBuf>,
     /// The preprocessor definitions.
     pub defines: IndexMap<String, Option<String>>,
+    /// The VHDL standard the source files are written in.
+    pub vhdl_version: Option<String>,
+    /// The SystemVerilog standard the source files are written in.
+    pub sv_version: Option<String>,
     /// The source files.
     pub files: Vec<SourceFile>,
 }
@@ -156,6 +163,8 @@ impl PrefixPaths for Sources {
             target: self.target,
             include_dirs: self.include_dirs.prefix_paths(prefix)?,
             defines: self.defines,
+            vhdl_version: self.vhdl_version,
+            sv_version: self.sv_version,
             files: self.files.prefix_paths(prefix)?,
         })
     }
@@ -469,6 +478,12 @@ impl Validate for PartialDependency {
     }
 }
 
+/// The VHDL standards that can be selected for a group of source files.
+pub const VHDL_VERSIONS: &[&str] = &["87", "93", "2002", "2008"];
+
+/// The SystemVerilog standards that can be selected for a group of source files.
+pub const SV_VERSIONS: &[&str] = &["2005", "2009", "2012", "2017"];
+
 /// A partial group of source files.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct PartialSources {
@@ -478,6 +493,10 @@ pub struct PartialSources {
     pub include_dirs: Option<Vec<String>>,
     /// The preprocessor definitions.
     pub defines: Option<IndexMap<String, Option<String>>>,
+    /// The VHDL standard the source files are written in.
+    pub vhdl_version: Option<String>,
+    /// The SystemVerilog standard the source files are written in.
+    pub sv_version: Option<String>,
     /// The source file paths.
     pub files: Vec<PartialSourceFile>,
 }
@@ -488,6 +507,8 @@ impl From<Vec<PartialSourceFile>> for PartialSources {
             target: None,
             include_dirs: None,
             defines: None,
+            vhdl_version: None,
+            sv_version: None,
             files: v,
         }
     }
@@ -504,11 +525,29 @@ impl Validate for PartialSources {
             .map(|path| env_path_from_string(path.to_string()))
             .collect();
         let defines = self.defines.unwrap_or_default();
+        if let Some(ref version) = self.vhdl_version {
+            if !VHDL_VERSIONS.contains(&version.as_str()) {
+                return Err(Error::new(format!(
+                    "Invalid VHDL version `{}`, expected one of {:?}.",
+                    version, VHDL_VERSIONS
+                )));
+            }
+        }
+        if let Some(ref version) = self.sv_version {
+            if !SV_VERSIONS.contains(&version.as_str()) {
+                return Err(Error::new(format!(
+                    "Invalid SystemVerilog version `{}`, expected one of {:?}.",
+                    version, SV_VERSIONS
+                )));
+            }
+        }
         let files: Result<Vec<_>> = self.files.into_iter().map(|f| f.validate()).collect();
         Ok(Sources {
             target: self.target.unwrap_or(TargetSpec::Wildcard),
             include_dirs: include_dirs?,
             defines,
+            vhdl_version: self.vhdl_version,
+            sv_version: self.sv_version,
             files: files?,
         })
     }
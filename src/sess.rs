@@ -34,7 +34,7 @@ use crate::config::{self, Config, Manifest};
 use crate::error::*;
 // use crate::future_throttle::FutureThrottle;
 use crate::git::Git;
-use crate::src::SourceGroup;
+use crate::src::{SourceFile, SourceGroup};
 use crate::target::TargetSpec;
 use crate::util::try_modification_time;
 
@@ -402,6 +402,8 @@ impl<'sess, 'ctx: 'sess> Session<'ctx> {
             include_dirs: include_dirs.clone(),
             export_incdirs: dependency_export_includes.clone(),
             defines,
+            vhdl_version: sources.vhdl_version.as_deref(),
+            sv_version: sources.sv_version.as_deref(),
             files,
             dependencies,
             version,
@@ -1267,6 +1269,8 @@ impl<'io, 'sess: 'io, 'ctx: 'sess> SessionIo<'sess, 'ctx> {
                     include_dirs: IndexSet::new(),
                     export_incdirs: IndexMap::new(),
                     defines: IndexMap::new(),
+                    vhdl_version: None,
+                    sv_version: None,
                     files,
                     dependencies: IndexSet::new(),
                     version: None,
@@ -1283,6 +1287,8 @@ impl<'io, 'sess: 'io, 'ctx: 'sess> SessionIo<'sess, 'ctx> {
             include_dirs: IndexSet::new(),
             export_incdirs: IndexMap::new(),
             defines: IndexMap::new(),
+            vhdl_version: None,
+            sv_version: None,
             files,
             dependencies: IndexSet::new(),
             version: None,
@@ -1293,6 +1299,71 @@ impl<'io, 'sess: 'io, 'ctx: 'sess> SessionIo<'sess, 'ctx> {
         Ok(sources)
     }
 
+    /// Load the source file manifest through the on-disk source cache.
+    ///
+    /// The cache in `.bender/sources_cache.json` is keyed by a hash of the root
+    /// manifest, the configuration, the locked dependencies, and the manifests
+    /// of path dependencies. If the key does not match, or a cached file no
+    /// longer exists, the sources are loaded with `sources()` and the cache is
+    /// updated. Failures to read or write the cache are not fatal.
+    pub async fn cached_sources(&'io self) -> Result<SourceGroup<'ctx>> {
+        let cache_path = self.sess.root.join(".bender").join("sources_cache.json");
+        let key = self.sources_cache_key();
+
+        let cached = std::fs::read(&cache_path)
+            .ok()
+            .and_then(|data| serde_json::from_slice::<SourcesCache>(&data).ok())
+            .filter(|cache| cache.key == key && cache.sources.files_exist());
+        if let Some(cache) = cached {
+            debugln!("sess: using source cache {:?}", cache_path);
+            let sources = cache.sources.intern(self.sess);
+            *self.sess.sources.lock().unwrap() = Some(sources.clone());
+            return Ok(sources);
+        }
+
+        let sources = self.sources().await?;
+        let cache = SourcesCache {
+            key,
+            sources: (&sources).into(),
+        };
+        let written = std::fs::create_dir_all(cache_path.parent().unwrap())
+            .map_err(|cause| Error::chain("Failed to create cache directory.", cause))
+            .and_then(|_| {
+                std::fs::File::create(&cache_path)
+                    .map_err(|cause| Error::chain("Failed to create source cache.", cause))
+            })
+            .and_then(|file| {
+                serde_json::to_writer(file, &cache)
+                    .map_err(|cause| Error::chain("Failed to write source cache.", cause))
+            });
+        if let Err(e) = written {
+            debugln!("sess: cannot write source cache {:?}: {}", cache_path, e);
+        }
+        Ok(sources)
+    }
+
+    /// Compute the key of the on-disk source cache.
+    fn sources_cache_key(&'io self) -> String {
+        use blake2::{Blake2b512, Digest};
+        let mut hasher = Blake2b512::new();
+        hasher.update(env!("CARGO_PKG_VERSION").as_bytes());
+        hasher.update(format!("{:?}", self.sess.manifest).as_bytes());
+        hasher.update(format!("{:?}", self.sess.config).as_bytes());
+        for &dep_id in self.sess.packages().iter().flatten() {
+            let dep = self.sess.dependency(dep_id);
+            hasher.update(format!("{:?}", dep).as_bytes());
+            // Path dependencies are not pinned to a revision, so their
+            // manifests have to be part of the key.
+            if let DependencySource::Path(_) = dep.source {
+                let manifest_path = self.get_package_path(dep_id).join("Bender.yml");
+                if let Ok(manifest) = read_manifest(&manifest_path) {
+                    hasher.update(format!("{:?}", manifest).as_bytes());
+                }
+            }
+        }
+        format!("{:016x}", hasher.finalize())
+    }
+
     /// Load the plugins declared by any of the dependencies.
     pub async fn plugins(&'io self) -> Result<&'ctx Plugins> {
         // Check if we already have the list of plugins.
@@ -1707,3 +1778,118 @@ pub struct Plugin {
     /// What binary implements the plugin.
     pub path: PathBuf,
 }
+
+/// The contents of the on-disk source cache.
+#[derive(Serialize, Deserialize)]
+struct SourcesCache {
+    /// The hash of the inputs the sources were loaded from.
+    key: String,
+    /// The loaded sources.
+    sources: CachedSourceGroup,
+}
+
+/// An owned copy of a `SourceGroup`, as stored in the source cache.
+#[derive(Serialize, Deserialize)]
+struct CachedSourceGroup {
+    package: Option<String>,
+    independent: bool,
+    target: TargetSpec,
+    include_dirs: Vec<PathBuf>,
+    export_incdirs: IndexMap<String, Vec<PathBuf>>,
+    defines: IndexMap<String, Option<String>>,
+    vhdl_version: Option<String>,
+    sv_version: Option<String>,
+    files: Vec<CachedSourceFile>,
+    dependencies: IndexSet<String>,
+    version: Option<Version>,
+}
+
+/// An owned copy of a `SourceFile`, as stored in the source cache.
+#[derive(Serialize, Deserialize)]
+enum CachedSourceFile {
+    File(PathBuf),
+    Group(Box<CachedSourceGroup>),
+}
+
+impl<'a, 'ctx> From<&'a SourceGroup<'ctx>> for CachedSourceGroup {
+    fn from(group: &'a SourceGroup<'ctx>) -> CachedSourceGroup {
+        CachedSourceGroup {
+            package: group.package.map(String::from),
+            independent: group.independent,
+            target: group.target.clone(),
+            include_dirs: group.include_dirs.iter().map(|&d| d.into()).collect(),
+            export_incdirs: group
+                .export_incdirs
+                .iter()
+                .map(|(pkg, dirs)| (pkg.clone(), dirs.iter().map(|&d| d.into()).collect()))
+                .collect(),
+            defines: group
+                .defines
+                .iter()
+                .map(|(&k, &v)| (k.into(), v.map(String::from)))
+                .collect(),
+            vhdl_version: group.vhdl_version.map(String::from),
+            sv_version: group.sv_version.map(String::from),
+            files: group
+                .files
+                .iter()
+                .map(|file| match *file {
+                    SourceFile::File(path) => CachedSourceFile::File(path.into()),
+                    SourceFile::Group(ref group) => {
+                        CachedSourceFile::Group(Box::new(group.as_ref().into()))
+                    }
+                })
+                .collect(),
+            dependencies: group.dependencies.clone(),
+            version: group.version.clone(),
+        }
+    }
+}
+
+impl CachedSourceGroup {
+    /// Check whether all files of the group still exist.
+    fn files_exist(&self) -> bool {
+        self.files.iter().all(|file| match *file {
+            CachedSourceFile::File(ref path) => path.exists(),
+            CachedSourceFile::Group(ref group) => group.files_exist(),
+        })
+    }
+
+    /// Internalize the group into a session.
+    fn intern<'ctx>(self, sess: &Session<'ctx>) -> SourceGroup<'ctx> {
+        SourceGroup {
+            package: self.package.map(|pkg| sess.intern_string(pkg)),
+            independent: self.independent,
+            target: self.target,
+            include_dirs: self
+                .include_dirs
+                .into_iter()
+                .map(|d| sess.intern_path(d))
+                .collect(),
+            export_incdirs: self
+                .export_incdirs
+                .into_iter()
+                .map(|(pkg, dirs)| (pkg, dirs.into_iter().map(|d| sess.intern_path(d)).collect()))
+                .collect(),
+            defines: self
+                .defines
+                .into_iter()
+                .map(|(k, v)| (sess.intern_string(k), v.map(|v| sess.intern_string(v))))
+                .collect(),
+            vhdl_version: self.vhdl_version.map(|v| sess.intern_string(v)),
+            sv_version: self.sv_version.map(|v| sess.intern_string(v)),
+            files: self
+                .files
+                .into_iter()
+                .map(|file| match file {
+                    CachedSourceFile::File(path) => SourceFile::File(sess.intern_path(path)),
+                    CachedSourceFile::Group(group) => {
+                        SourceFile::Group(Box::new(group.intern(sess)))
+                    }
+                })
+                .collect(),
+            dependencies: self.dependencies,
+            version: self.version,
+        }
+    }
+}
@@ -14,7 +14,6 @@ use std::path::Path;
 use indexmap::{IndexMap, IndexSet};
 use serde::ser::{Serialize, Serializer};
 
-use crate::sess::Session;
 use crate::target::{TargetSet, TargetSpec};
 use semver;
 
@@ -33,6 +32,12 @@ pub struct SourceGroup<'ctx> {
     pub export_incdirs: IndexMap<String, IndexSet<&'ctx Path>>,
     /// The preprocessor definitions.
     pub defines: IndexMap<&'ctx str, Option<&'ctx str>>,
+    /// The VHDL standard the files in this group are written in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vhdl_version: Option<&'ctx str>,
+    /// The SystemVerilog standard the files in this group are written in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sv_version: Option<&'ctx str>,
     /// The files in this group.
     pub files: Vec<SourceFile<'ctx>>,
     /// Package dependencies of this source group
@@ -61,6 +66,8 @@ impl<'ctx> SourceGroup<'ctx> {
                     if group.files.len() == 1
                         && group.include_dirs.is_empty()
                         && group.defines.is_empty()
+                        && group.vhdl_version.is_none()
+                        && group.sv_version.is_none()
                         && group.target.is_wildcard()
                         && group.package.is_none()
                     {
@@ -78,7 +85,27 @@ impl<'ctx> SourceGroup<'ctx> {
 
     /// Filter the sources, keeping only the ones that apply to a target.
     pub fn filter_targets(&self, targets: &TargetSet) -> Option<SourceGroup<'ctx>> {
-        if !self.target.matches(targets) {
+        self.filter_targets_by(&|spec| spec.matches(targets))
+    }
+
+    /// Filter the sources, keeping only the ones that apply to any of the
+    /// given target sets.
+    pub fn filter_targets_any(&self, target_sets: &[TargetSet]) -> Option<SourceGroup<'ctx>> {
+        self.filter_targets_by(&|spec| target_sets.iter().any(|t| spec.matches(t)))
+    }
+
+    /// Filter the sources, removing the ones whose target references any of
+    /// the given targets.
+    pub fn exclude_targets(&self, targets: &TargetSet) -> Option<SourceGroup<'ctx>> {
+        self.filter_targets_by(&|spec| !targets.iter().any(|t| spec.references(t)))
+    }
+
+    /// Filter the sources, keeping only the ones whose target matches.
+    fn filter_targets_by(
+        &self,
+        matches: &dyn Fn(&TargetSpec) -> bool,
+    ) -> Option<SourceGroup<'ctx>> {
+        if !matches(&self.target) {
             return None;
         }
         let files = self
@@ -86,7 +113,7 @@ impl<'ctx> SourceGroup<'ctx> {
             .iter()
             .filter_map(|file| match *file {
                 SourceFile::Group(ref group) => group
-                    .filter_targets(targets)
+                    .filter_targets_by(matches)
                     .map(|g| SourceFile::Group(Box::new(g))),
                 ref other => Some(other.clone()),
             })
@@ -99,6 +126,8 @@ impl<'ctx> SourceGroup<'ctx> {
                 include_dirs: self.include_dirs.clone(),
                 export_incdirs: self.export_incdirs.clone(),
                 defines: self.defines.clone(),
+                vhdl_version: self.vhdl_version,
+                sv_version: self.sv_version,
                 files,
                 dependencies: self.dependencies.clone(),
                 version: self.version.clone(),
@@ -124,7 +153,7 @@ impl<'ctx> SourceGroup<'ctx> {
 
         for file in &self.files {
             if let SourceFile::Group(group) = file {
-                result.extend(group.get_deps(&result, excludes));
+                result.extend(group.get_deps(packages, excludes));
             }
         }
 
@@ -132,29 +161,32 @@ impl<'ctx> SourceGroup<'ctx> {
     }
 
     /// Get list of packages based on constraints.
+    ///
+    /// Dependencies are followed up to `depth` levels below the selected
+    /// packages, or without limit if `depth` is `None`.
     pub fn get_package_list(
         &self,
-        sess: &Session,
+        root_package: &str,
         packages: &IndexSet<String>,
         excludes: &IndexSet<String>,
-        no_deps: bool,
+        depth: Option<usize>,
     ) -> IndexSet<String> {
         let mut result = IndexSet::new();
 
         if !packages.is_empty() {
             result.extend(packages.clone());
         } else {
-            result.insert(sess.manifest.package.name.to_string());
+            result.insert(root_package.to_string());
         }
 
         result = &result - excludes;
 
-        if !no_deps {
-            let mut curr_length = 0;
-            while curr_length < result.len() {
-                curr_length = result.len();
-                result.extend(self.get_deps(&result, excludes));
-            }
+        let mut curr_length = 0;
+        let mut level = 0;
+        while curr_length < result.len() && depth.is_none_or(|depth| level < depth) {
+            curr_length = result.len();
+            result.extend(self.get_deps(&result, excludes));
+            level += 1;
         }
 
         result
@@ -186,6 +218,8 @@ impl<'ctx> SourceGroup<'ctx> {
                 include_dirs: self.include_dirs.clone(),
                 export_incdirs,
                 defines: self.defines.clone(),
+                vhdl_version: self.vhdl_version,
+                sv_version: self.sv_version,
                 files,
                 dependencies: self.dependencies.clone(),
                 version: self.version.clone(),
@@ -195,6 +229,11 @@ impl<'ctx> SourceGroup<'ctx> {
     }
 
     /// Return list of unique include directories for the current src
+    ///
+    /// These are the group's own `include_dirs` and the `export_incdirs` of its
+    /// package and direct dependencies. The private `include_dirs` of a
+    /// dependency are therefore never part of a dependent's include
+    /// directories.
     pub fn get_incdirs(self) -> Vec<&'ctx Path> {
         let incdirs = self
             .include_dirs
@@ -262,6 +301,8 @@ impl<'ctx> SourceGroup<'ctx> {
                         .map(|(k, v)| (*k, *v))
                         .chain(grp.defines.into_iter())
                         .collect();
+                    grp.vhdl_version = grp.vhdl_version.or(self.vhdl_version);
+                    grp.sv_version = grp.sv_version.or(self.sv_version);
                     grp.flatten_into(into);
                 }
             }
@@ -314,3 +355,55 @@ impl<'ctx> Serialize for SourceFile<'ctx> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A source group of a package without files.
+    fn group<'a>(package: &'a str, dependencies: &[&str]) -> SourceGroup<'a> {
+        SourceGroup {
+            package: Some(package),
+            independent: true,
+            target: TargetSpec::Wildcard,
+            include_dirs: IndexSet::new(),
+            export_incdirs: IndexMap::new(),
+            defines: IndexMap::new(),
+            vhdl_version: None,
+            sv_version: None,
+            files: vec![],
+            dependencies: dependencies.iter().map(|dep| dep.to_string()).collect(),
+            version: None,
+        }
+    }
+
+    #[test]
+    fn package_list_depth() {
+        // The order of the groups must not matter for the depth.
+        for order in [["top", "mid", "leaf"], ["leaf", "mid", "top"]] {
+            let mut srcs = group("top", &[]);
+            srcs.package = None;
+            srcs.files = order
+                .iter()
+                .map(|&package| {
+                    let deps: &[&str] = match package {
+                        "top" => &["mid"],
+                        "mid" => &["leaf"],
+                        _ => &[],
+                    };
+                    SourceFile::Group(Box::new(group(package, deps)))
+                })
+                .collect();
+            let list = |depth| -> Vec<String> {
+                srcs.get_package_list("top", &IndexSet::new(), &IndexSet::new(), depth)
+                    .into_iter()
+                    .collect()
+            };
+            assert_eq!(list(Some(0)), ["top"]);
+            assert_eq!(list(Some(1)), ["top", "mid"]);
+            assert_eq!(list(Some(2)), ["top", "mid", "leaf"]);
+            assert_eq!(list(Some(3)), ["top", "mid", "leaf"]);
+            assert_eq!(list(None), ["top", "mid", "leaf"]);
+        }
+    }
+}
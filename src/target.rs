@@ -102,6 +102,10 @@ impl<'de> Deserialize<'de> for TargetSpec {
     {
         use serde::de;
         let s = String::deserialize(deserializer)?;
+        // The wildcard is serialized as `*`, which is not a valid expression.
+        if s == "*" {
+            return Ok(TargetSpec::Wildcard);
+        }
         TargetSpec::from_str(&s).map_err(de::Error::custom)
     }
 }
@@ -122,6 +126,124 @@ impl TargetSpec {
     pub fn is_wildcard(&self) -> bool {
         matches!(*self, TargetSpec::Wildcard)
     }
+
+    /// Simplify the specification by removing wildcards from `all` and
+    /// inlining nested `all` and single-element lists.
+    pub fn simplify(&self) -> TargetSpec {
+        match *self {
+            TargetSpec::All(ref specs) => {
+                let mut set = BTreeSet::new();
+                for spec in specs.iter().map(TargetSpec::simplify) {
+                    match spec {
+                        TargetSpec::Wildcard => {}
+                        TargetSpec::All(inner) => set.extend(inner),
+                        spec => {
+                            set.insert(spec);
+                        }
+                    }
+                }
+                match set.len() {
+                    0 => TargetSpec::Wildcard,
+                    1 => set.pop_first().unwrap(),
+                    _ => TargetSpec::All(set),
+                }
+            }
+            TargetSpec::Any(ref specs) => {
+                let set: BTreeSet<_> = specs.iter().map(TargetSpec::simplify).collect();
+                if set.iter().any(TargetSpec::is_wildcard) {
+                    TargetSpec::Wildcard
+                } else if set.len() == 1 {
+                    set.into_iter().next().unwrap()
+                } else {
+                    TargetSpec::Any(set)
+                }
+            }
+            TargetSpec::Not(ref spec) => TargetSpec::Not(Box::new(spec.simplify())),
+            ref spec => spec.clone(),
+        }
+    }
+
+    /// Parse a boolean target expression such as `asic && !(fpga || sim)`.
+    ///
+    /// The operators bind in the order `!`, `&&`, `||`, and parentheses may be
+    /// used for grouping. The result is expressed in terms of `all`, `any`, and
+    /// `not`.
+    pub fn from_expr(s: &str) -> Result<TargetSpec> {
+        let mut iter = s.chars();
+        let next = iter.next();
+        let mut lexer = TargetLexer {
+            inner: iter,
+            partial: None,
+            next,
+        }
+        .peekable();
+        let spec = parse_expr_or(&mut lexer).and_then(|spec| match lexer.next() {
+            None => Ok(spec),
+            wrong => parse_wrong(wrong),
+        });
+        spec.map_err(|cause| {
+            Error::chain(format!("Syntax error in target expression `{}`.", s), cause)
+        })
+    }
+
+    /// Check whether a target appears in a non-negated position of this
+    /// specification, i.e. whether setting the target can make it match.
+    pub fn references(&self, name: &str) -> bool {
+        self.references_with_polarity(name, true)
+    }
+
+    fn references_with_polarity(&self, name: &str, positive: bool) -> bool {
+        match *self {
+            TargetSpec::Wildcard => false,
+            TargetSpec::Name(ref n) => positive && n == name,
+            TargetSpec::All(ref specs) | TargetSpec::Any(ref specs) => specs
+                .iter()
+                .any(|s| s.references_with_polarity(name, positive)),
+            TargetSpec::Not(ref spec) => spec.references_with_polarity(name, !positive),
+        }
+    }
+
+    /// Get the target names referenced by this specification.
+    pub fn names(&self) -> BTreeSet<&str> {
+        match *self {
+            TargetSpec::Wildcard => BTreeSet::new(),
+            TargetSpec::Name(ref name) => std::iter::once(name.as_str()).collect(),
+            TargetSpec::All(ref specs) | TargetSpec::Any(ref specs) => {
+                specs.iter().flat_map(|s| s.names()).collect()
+            }
+            TargetSpec::Not(ref spec) => spec.names(),
+        }
+    }
+
+    /// Get all target sets that satisfy this specification.
+    ///
+    /// Only the targets referenced by the specification are considered, each
+    /// of which may either be set or unset. The returned sets additionally
+    /// contain all targets of `base`.
+    pub fn satisfying_sets(&self, base: &TargetSet) -> Result<Vec<TargetSet>> {
+        const MAX_NAMES: usize = 16;
+        let names: Vec<&str> = self.names().into_iter().collect();
+        if names.len() > MAX_NAMES {
+            return Err(Error::new(format!(
+                "Target specification `{}` references more than {} targets.",
+                self, MAX_NAMES
+            )));
+        }
+        Ok((0..1u32 << names.len())
+            .map(|mask| {
+                TargetSet::new(
+                    base.iter().map(String::as_str).chain(
+                        names
+                            .iter()
+                            .enumerate()
+                            .filter(|&(i, _)| mask & (1 << i) != 0)
+                            .map(|(_, &name)| name),
+                    ),
+                )
+            })
+            .filter(|set| self.matches(set))
+            .collect())
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -133,6 +255,9 @@ enum TargetToken {
     Any,
     All,
     Not,
+    AndAnd,
+    OrOr,
+    Bang,
 }
 
 struct TargetLexer<T>
@@ -193,6 +318,18 @@ where
                 Some('(') => return Some(Ok(TargetToken::LParen)),
                 Some(')') => return Some(Ok(TargetToken::RParen)),
                 Some(',') => return Some(Ok(TargetToken::Comma)),
+                Some('!') => return Some(Ok(TargetToken::Bang)),
+                Some(c @ '&') | Some(c @ '|') => {
+                    if self.next != Some(c) {
+                        return Some(Err(Error::new(format!("Invalid character `{}`.", c))));
+                    }
+                    self.next = self.inner.next();
+                    return Some(Ok(if c == '&' {
+                        TargetToken::AndAnd
+                    } else {
+                        TargetToken::OrOr
+                    }));
+                }
                 Some(c) if c.is_whitespace() => (),
                 Some(c) => return Some(Err(Error::new(format!("Invalid character `{}`.", c)))),
                 None => return None,
@@ -224,6 +361,61 @@ where
     })
 }
 
+type ExprLexer<T> = std::iter::Peekable<TargetLexer<T>>;
+
+fn parse_expr_or<T>(lexer: &mut ExprLexer<T>) -> Result<TargetSpec>
+where
+    T: Iterator<Item = char>,
+{
+    let mut set = BTreeSet::new();
+    set.insert(parse_expr_and(lexer)?);
+    while let Some(Ok(TargetToken::OrOr)) = lexer.peek() {
+        lexer.next();
+        set.insert(parse_expr_and(lexer)?);
+    }
+    Ok(if set.len() == 1 {
+        set.pop_first().unwrap()
+    } else {
+        TargetSpec::Any(set)
+    })
+}
+
+fn parse_expr_and<T>(lexer: &mut ExprLexer<T>) -> Result<TargetSpec>
+where
+    T: Iterator<Item = char>,
+{
+    let mut set = BTreeSet::new();
+    set.insert(parse_expr_unary(lexer)?);
+    while let Some(Ok(TargetToken::AndAnd)) = lexer.peek() {
+        lexer.next();
+        set.insert(parse_expr_unary(lexer)?);
+    }
+    Ok(if set.len() == 1 {
+        set.pop_first().unwrap()
+    } else {
+        TargetSpec::All(set)
+    })
+}
+
+fn parse_expr_unary<T>(lexer: &mut ExprLexer<T>) -> Result<TargetSpec>
+where
+    T: Iterator<Item = char>,
+{
+    Ok(match lexer.next() {
+        Some(Ok(TargetToken::Bang)) => TargetSpec::Not(Box::new(parse_expr_unary(lexer)?)),
+        Some(Ok(TargetToken::Ident(name))) => TargetSpec::Name(name),
+        Some(Ok(TargetToken::LParen)) => {
+            let spec = parse_expr_or(lexer)?;
+            match lexer.next() {
+                Some(Ok(TargetToken::RParen)) => spec,
+                Some(Err(e)) => return Err(e),
+                _ => return Err(Error::new("Expected `)`.")),
+            }
+        }
+        wrong => return parse_wrong(wrong),
+    })
+}
+
 fn parse_paren_list<T>(lexer: &mut TargetLexer<T>) -> Result<BTreeSet<TargetSpec>>
 where
     T: Iterator<Item = char>,
@@ -264,6 +456,9 @@ fn parse_wrong<R>(wrong: Option<Result<TargetToken>>) -> Result<R> {
         Some(Ok(TargetToken::LParen)) => Err(Error::new("Unexpected `(`.")),
         Some(Ok(TargetToken::RParen)) => Err(Error::new("Unexpected `)`.")),
         Some(Ok(TargetToken::Comma)) => Err(Error::new("Unexpected `,`.")),
+        Some(Ok(TargetToken::AndAnd)) => Err(Error::new("Unexpected `&&`.")),
+        Some(Ok(TargetToken::OrOr)) => Err(Error::new("Unexpected `||`.")),
+        Some(Ok(TargetToken::Bang)) => Err(Error::new("Unexpected `!`.")),
         Some(Err(e)) => Err(e),
         None => Err(Error::new("Unexpected end of string.")),
     }
@@ -297,6 +492,11 @@ impl TargetSet {
         TargetSet(targets)
     }
 
+    /// Check whether the set contains a target.
+    pub fn contains(&self, target: &str) -> bool {
+        self.0.contains(&target.to_lowercase())
+    }
+
     /// Returns true if the set of targets is empty.
     pub fn is_empty(&self) -> bool {
         self.0.is_empty()
@@ -323,3 +523,67 @@ impl IntoIterator for TargetSet {
         self.0.into_iter()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expr(s: &str) -> TargetSpec {
+        TargetSpec::from_expr(s).unwrap()
+    }
+
+    fn spec(s: &str) -> TargetSpec {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn expr_precedence() {
+        assert_eq!(expr("a || b && c"), spec("any(a, all(b, c))"));
+        assert_eq!(expr("a && b || c"), spec("any(all(a, b), c)"));
+        assert_eq!(expr("!a && b"), spec("all(not(a), b)"));
+        assert_eq!(expr("a || b || c"), spec("any(a, b, c)"));
+    }
+
+    #[test]
+    fn expr_negation() {
+        assert_eq!(expr("!a"), spec("not(a)"));
+        assert_eq!(expr("!!a"), spec("not(not(a))"));
+        assert_eq!(expr("!(a || b)"), spec("not(any(a, b))"));
+        // Negating an unset target matches.
+        assert!(expr("!fpga").matches(&TargetSet::new(["asic"])));
+        assert!(!expr("!fpga").matches(&TargetSet::new(["fpga"])));
+    }
+
+    #[test]
+    fn expr_parentheses() {
+        assert_eq!(expr("(a || b) && c"), spec("all(any(a, b), c)"));
+        assert_eq!(expr("((a))"), spec("a"));
+        assert_eq!(
+            expr("asic && !(fpga || (sim && !rtl))"),
+            spec("all(asic, not(any(fpga, all(sim, not(rtl)))))")
+        );
+    }
+
+    #[test]
+    fn expr_errors() {
+        for s in [
+            "", "a &&", "|| a", "a & b", "a | b", "(a", "a)", "a b", "!", "a && ()", "a, b",
+        ] {
+            assert!(
+                TargetSpec::from_expr(s).is_err(),
+                "`{}` should not parse",
+                s
+            );
+        }
+    }
+
+    #[test]
+    fn satisfying_sets_keep_base() {
+        let sets = expr("fpga || test_only")
+            .satisfying_sets(&TargetSet::new(["rtl"]))
+            .unwrap();
+        assert_eq!(sets.len(), 3);
+        assert!(sets.iter().all(|set| set.contains("rtl")));
+        assert!(sets.iter().all(|set| !set.contains("asic")));
+    }
+}
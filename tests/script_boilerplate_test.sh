@@ -0,0 +1,47 @@
+#!/bin/bash
+set -e
+
+cd "$(dirname "${BASH_SOURCE[0]}")"
+BENDER="cargo run --"
+DIR="$(pwd)"/tmp/"$(basename $0 _test.sh)"
+[ ! -d "$DIR" ] || rm -rf "$DIR"
+mkdir -p "$DIR"
+cd "$DIR"
+
+mkdir src
+touch src/top.sv
+echo "
+package:
+  name: top
+
+sources:
+  - src/top.sv
+" > Bender.yml
+echo "# prepended" > prepend.txt
+echo "# appended" > append.txt
+
+# The prepended content follows the shebang and precedes the header.
+$BENDER script vcs --prepend-file prepend.txt --append-file append.txt > compile.sh
+if [ "$(sed -n 1p compile.sh)" != "#!/usr/bin/env bash" ]; then
+	cat compile.sh
+	echo "should keep the shebang in the first line" >&2
+	exit 1
+fi
+if [ "$(sed -n 2p compile.sh)" != "# prepended" ] || ! sed -n 3p compile.sh | grep -q 'generated automatically by bender'; then
+	cat compile.sh
+	echo "should prepend the content before the header" >&2
+	exit 2
+fi
+if [ "$(tail -n 1 compile.sh)" != "# appended" ]; then
+	cat compile.sh
+	echo "should append the content at the end" >&2
+	exit 3
+fi
+
+# Without a shebang, the prepended content comes first.
+$BENDER script flist --prepend-file prepend.txt > sources.f
+if [ "$(sed -n 1p sources.f)" != "# prepended" ]; then
+	cat sources.f
+	echo "should prepend the content in the first line" >&2
+	exit 4
+fi
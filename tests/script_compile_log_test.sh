@@ -0,0 +1,36 @@
+#!/bin/bash
+set -e
+
+cd "$(dirname "${BASH_SOURCE[0]}")"
+BENDER="cargo run --"
+DIR="$(pwd)"/tmp/"$(basename $0 _test.sh)"
+[ ! -d "$DIR" ] || rm -rf "$DIR"
+mkdir -p "$DIR"
+cd "$DIR"
+
+mkdir src
+touch src/a.sv src/b.vhd
+echo "
+package:
+  name: top
+
+sources:
+  - src/a.sv
+  - src/b.vhd
+" > Bender.yml
+
+# The vsim transcript is redirected once, instead of rewriting the log per call.
+$BENDER script vsim --compile-log compile.log > vsim.tcl
+if [ "$(grep -c '^transcript file compile.log$' vsim.tcl)" != 1 ] || grep -q -- '-l compile.log' vsim.tcl; then
+	cat vsim.tcl
+	echo "vsim should redirect the transcript to compile.log once" >&2
+	exit 1
+fi
+
+# A failing call must not be hidden by tee.
+$BENDER script vcs --compile-log compile.log > vcs.sh
+if ! grep -q '^set -o pipefail$' vcs.sh; then
+	cat vcs.sh
+	echo "vcs should set pipefail with --compile-log" >&2
+	exit 2
+fi
@@ -0,0 +1,31 @@
+#!/bin/bash
+set -e
+
+cd "$(dirname "${BASH_SOURCE[0]}")"
+BENDER="cargo run --"
+DIR="$(pwd)"/tmp/"$(basename $0 _test.sh)"
+[ ! -d "$DIR" ] || rm -rf "$DIR"
+mkdir -p "$DIR"
+cd "$DIR"
+
+mkdir src
+touch src/pkg.sv src/a.sv src/b.sv
+echo "
+package:
+  name: top
+
+sources:
+  - src/pkg.sv
+  - src/a.sv
+  - files:
+      - src/pkg.sv
+      - src/b.sv
+" > Bender.yml
+
+# A re-listed file is kept at its last position, after the files it follows.
+$BENDER script flist > flist
+printf '%s\n' "$DIR"/src/a.sv "$DIR"/src/pkg.sv "$DIR"/src/b.sv > expected
+if ! diff expected <(grep -v '^$' flist); then
+	echo "should keep the last occurrence of src/pkg.sv" >&2
+	exit 1
+fi
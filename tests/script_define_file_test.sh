@@ -0,0 +1,43 @@
+#!/bin/bash
+set -e
+
+cd "$(dirname "${BASH_SOURCE[0]}")"
+BENDER="cargo run --"
+DIR="$(pwd)"/tmp/"$(basename $0 _test.sh)"
+[ ! -d "$DIR" ] || rm -rf "$DIR"
+mkdir -p "$DIR"
+cd "$DIR"
+
+mkdir src
+touch src/top.sv
+echo "
+package:
+  name: top
+
+sources:
+  - src/top.sv
+" > Bender.yml
+echo "
+# comment lines and blank lines are skipped
+WIDTH=8
+DEPTH=4
+
+BARE
+" > first.defs
+echo "
+DEPTH=16
+" > second.defs
+
+$BENDER script flist-plus --no-target-defines \
+	--define-file first.defs --define-file second.defs -D WIDTH=32 > flist
+# Later files override earlier ones, and -D overrides all files.
+printf '%s\n' +define+WIDTH=32 +define+DEPTH=16 +define+BARE > expected
+if ! diff expected <(grep '^+define+' flist); then
+	echo "should read defines from files, overridden in order" >&2
+	exit 1
+fi
+if grep -q '#' flist; then
+	cat flist
+	echo "should skip comments" >&2
+	exit 2
+fi
@@ -0,0 +1,40 @@
+#!/bin/bash
+set -e
+
+cd "$(dirname "${BASH_SOURCE[0]}")"
+BENDER="cargo run --"
+DIR="$(pwd)"/tmp/"$(basename $0 _test.sh)"
+[ ! -d "$DIR" ] || rm -rf "$DIR"
+mkdir -p "$DIR"
+cd "$DIR"
+
+mkdir src
+touch src/top.sv src/rtl.sv src/test_only.sv
+echo "
+package:
+  name: top
+
+sources:
+  - src/top.sv
+  - target: rtl
+    files:
+      - src/rtl.sv
+  - target: all(rtl, test_only)
+    files:
+      - src/test_only.sv
+" > Bender.yml
+
+$BENDER script flist --target rtl --target test_only > flist
+if ! grep -q test_only.sv flist; then
+	cat flist
+	echo "should include src/test_only.sv without exclusion" >&2
+	exit 1
+fi
+
+# Exclusion wins over a matching included target.
+$BENDER script flist --target rtl --exclude-target test_only > flist
+printf '%s\n' "$DIR"/src/top.sv "$DIR"/src/rtl.sv > expected
+if ! diff expected <(grep -v '^$' flist); then
+	echo "should drop src/test_only.sv" >&2
+	exit 2
+fi
@@ -0,0 +1,36 @@
+#!/bin/bash
+set -e
+
+cd "$(dirname "${BASH_SOURCE[0]}")"
+BENDER="cargo run --"
+DIR="$(pwd)"/tmp/"$(basename $0 _test.sh)"
+[ ! -d "$DIR" ] || rm -rf "$DIR"
+mkdir -p "$DIR"
+cd "$DIR"
+
+mkdir src
+touch src/top.sv
+echo "
+package:
+  name: top
+
+sources:
+  - target: sim
+    files:
+      - src/top.sv
+" > Bender.yml
+
+# Without the flag, an empty script is not an error.
+$BENDER script flist --target nonexistent > empty.f
+
+# With the flag, the active targets are reported.
+if $BENDER script flist --target nonexistent --fail-on-empty > out.f 2> err.txt; then
+	cat out.f
+	echo "should fail without any source files" >&2
+	exit 1
+fi
+if ! grep -q 'No source files remain after filtering' err.txt || ! grep -q 'active targets:.*nonexistent' err.txt; then
+	cat err.txt
+	echo "should report the active targets" >&2
+	exit 2
+fi
@@ -0,0 +1,31 @@
+#!/bin/bash
+set -e
+
+cd "$(dirname "${BASH_SOURCE[0]}")"
+BENDER="cargo run --"
+DIR="$(pwd)"/tmp/"$(basename $0 _test.sh)"
+[ ! -d "$DIR" ] || rm -rf "$DIR"
+mkdir -p "$DIR"
+cd "$DIR"
+
+mkdir src
+touch src/top.sv
+echo "
+package:
+  name: top
+
+sources:
+  - src/top.sv
+" > Bender.yml
+
+$BENDER script genus --relative-path > genus.tcl
+if ! grep -q '^set ROOT "' genus.tcl; then
+	cat genus.tcl
+	echo "should set ROOT" >&2
+	exit 1
+fi
+if grep -q '^set ROOT =' genus.tcl; then
+	cat genus.tcl
+	echo "should not pass = to set" >&2
+	exit 2
+fi
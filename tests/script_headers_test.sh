@@ -0,0 +1,50 @@
+#!/bin/bash
+set -e
+
+cd "$(dirname "${BASH_SOURCE[0]}")"
+BENDER="cargo run --"
+DIR="$(pwd)"/tmp/"$(basename $0 _test.sh)"
+[ ! -d "$DIR" ] || rm -rf "$DIR"
+mkdir -p "$DIR"
+cd "$DIR"
+
+mkdir src include
+touch src/top.sv src/local.svh include/defs.svh
+echo "
+package:
+  name: top
+
+sources:
+  - include_dirs:
+      - include
+    files:
+      - include/defs.svh
+      - src/local.svh
+      - src/top.sv
+" > Bender.yml
+
+# File lists keep the headers.
+$BENDER script flist > flist
+if ! grep -q defs.svh flist; then
+	cat flist
+	echo "flist should keep include/defs.svh" >&2
+	exit 1
+fi
+
+# Compiling formats drop them, without adding undeclared include directories.
+$BENDER script vsim > vsim.tcl 2> stderr
+if grep -q '\.svh' vsim.tcl; then
+	cat vsim.tcl
+	echo "vsim should not compile headers" >&2
+	exit 2
+fi
+if grep -q "+incdir+$DIR/src\"" vsim.tcl; then
+	cat vsim.tcl
+	echo "vsim should not add src as include directory" >&2
+	exit 3
+fi
+if ! grep -q 'local.svh' stderr || grep -q 'defs.svh' stderr; then
+	cat stderr
+	echo "should only warn about src/local.svh" >&2
+	exit 4
+fi
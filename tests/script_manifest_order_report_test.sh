@@ -0,0 +1,43 @@
+#!/bin/bash
+set -e
+
+cd "$(dirname "${BASH_SOURCE[0]}")"
+BENDER="cargo run --"
+DIR="$(pwd)"/tmp/"$(basename $0 _test.sh)"
+[ ! -d "$DIR" ] || rm -rf "$DIR"
+mkdir -p "$DIR"
+cd "$DIR"
+
+mkdir src
+touch src/pkg.vhd src/a.sv src/b.sv
+echo "
+package:
+  name: top
+
+sources:
+  - files:
+      - src/pkg.vhd
+      - src/a.sv
+  - src/b.sv
+" > Bender.yml
+
+$BENDER script vsim --manifest-order-report > vsim.tcl 2> report
+for GROUP in "\[0\] top (vhdl)" "\[1\] top (verilog)" "\[2\] top (verilog)"; do
+	if ! grep -q "$GROUP" report; then
+		cat report
+		echo "should report group $GROUP" >&2
+		exit 1
+	fi
+done
+if ! grep -q "split into 2 groups" report; then
+	cat report
+	echo "should note the split group" >&2
+	exit 2
+fi
+
+# The report does not end up in the script.
+if grep -q "Compile order" vsim.tcl || ! grep -q vlog vsim.tcl; then
+	cat vsim.tcl
+	echo "should only write the script to stdout" >&2
+	exit 3
+fi
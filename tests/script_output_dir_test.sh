@@ -0,0 +1,49 @@
+#!/bin/bash
+set -e
+
+cd "$(dirname "${BASH_SOURCE[0]}")"
+BENDER="cargo run --"
+DIR="$(pwd)"/tmp/"$(basename $0 _test.sh)"
+[ ! -d "$DIR" ] || rm -rf "$DIR"
+mkdir -p "$DIR"
+cd "$DIR"
+
+mkdir src
+touch src/top.sv
+echo "
+package:
+  name: top
+
+sources:
+  - src/top.sv
+" > Bender.yml
+
+# Both formats are emitted from one invocation.
+$BENDER script vsim flist --output-dir out
+for FILE in out/compile.tcl out/sources.f; do
+	if [ ! -f "$FILE" ]; then
+		ls out
+		echo "should write $FILE" >&2
+		exit 1
+	fi
+done
+if ! grep -q 'vlog' out/compile.tcl; then
+	cat out/compile.tcl
+	echo "should write the vsim script to out/compile.tcl" >&2
+	exit 2
+fi
+
+# Format-specific options are ignored by the formats that do not use them.
+$BENDER script vsim flist --top top --output-dir out_top
+for FILE in out_top/compile.tcl out_top/sources.f; do
+	if [ ! -f "$FILE" ]; then
+		ls out_top
+		echo "should write $FILE with --top" >&2
+		exit 3
+	fi
+done
+if ! grep -q 'vopt.* top' out_top/compile.tcl; then
+	cat out_top/compile.tcl
+	echo "should optimize the top-level module in out_top/compile.tcl" >&2
+	exit 4
+fi
@@ -0,0 +1,39 @@
+#!/bin/bash
+set -e
+
+cd "$(dirname "${BASH_SOURCE[0]}")"
+BENDER="cargo run --"
+DIR="$(pwd)"/tmp/"$(basename $0 _test.sh)"
+[ ! -d "$DIR" ] || rm -rf "$DIR"
+mkdir -p "$DIR"
+cd "$DIR"
+
+mkdir src
+touch src/top.sv src/fpga.sv
+echo "
+package:
+  name: top
+
+sources:
+  - src/top.sv
+  - target: all(fpga, not(sim))
+    files:
+      - src/fpga.sv
+" > Bender.yml
+
+$BENDER script vsim --print-targets > targets.txt
+if ! grep -qx 'all(fpga, not(sim))' targets.txt; then
+	cat targets.txt
+	echo "should print the target specification" >&2
+	exit 1
+fi
+if ! grep -qx 'top:' targets.txt || ! grep -qx '    all(fpga, not(sim))' targets.txt; then
+	cat targets.txt
+	echo "should print the target specification of the package" >&2
+	exit 2
+fi
+if grep -q 'vlog' targets.txt; then
+	cat targets.txt
+	echo "should not print a script" >&2
+	exit 3
+fi
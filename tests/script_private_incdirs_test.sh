@@ -0,0 +1,55 @@
+#!/bin/bash
+set -e
+
+cd "$(dirname "${BASH_SOURCE[0]}")"
+BENDER="cargo run --"
+DIR="$(pwd)"/tmp/"$(basename $0 _test.sh)"
+[ ! -d "$DIR" ] || rm -rf "$DIR"
+mkdir -p "$DIR"
+cd "$DIR"
+
+mkdir -p dep/src dep/private dep/include top
+touch dep/src/dep.sv top/top.sv
+echo "
+package:
+  name: dep
+
+export_include_dirs:
+  - include
+
+sources:
+  - include_dirs:
+      - private
+    files:
+      - src/dep.sv
+" > dep/Bender.yml
+echo "
+package:
+  name: top
+
+dependencies:
+  dep: { path: ../dep }
+
+sources:
+  - top.sv
+" > top/Bender.yml
+
+cd top
+$BENDER script flist-plus > flist
+if ! grep -q "^+incdir+$DIR/dep/include$" flist; then
+	cat flist
+	echo "should include the exported include directory of dep" >&2
+	exit 1
+fi
+if grep -q "^+incdir+$DIR/dep/private$" flist; then
+	cat flist
+	echo "should not include the private include directory of dep" >&2
+	exit 2
+fi
+
+$BENDER script flist-plus --no-scope-incdirs > flist
+if ! grep -q "^+incdir+$DIR/dep/private$" flist; then
+	cat flist
+	echo "should include the private include directory of dep with --no-scope-incdirs" >&2
+	exit 3
+fi
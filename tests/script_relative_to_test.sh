@@ -0,0 +1,39 @@
+#!/bin/bash
+set -e
+
+cd "$(dirname "${BASH_SOURCE[0]}")"
+BENDER="cargo run --"
+DIR="$(pwd)"/tmp/"$(basename $0 _test.sh)"
+[ ! -d "$DIR" ] || rm -rf "$DIR"
+mkdir -p "$DIR"
+cd "$DIR"
+
+mkdir src include build
+touch src/foo.sv
+echo "
+package:
+  name: top
+
+sources:
+  - include_dirs:
+      - include
+    files:
+      - src/foo.sv
+" > Bender.yml
+
+# A build directory next to the sources.
+$BENDER script flist-plus --relative-to build > build/flist
+printf '%s\n' "+incdir+../include" "+define+TARGET_FLIST" "../src/foo.sv" > expected
+if ! diff expected <(grep -v '^$' build/flist); then
+	echo "should emit paths relative to the build directory" >&2
+	exit 1
+fi
+
+# The directory is resolved against the working directory.
+cd build
+$BENDER script vsim --relative-to . > vsim.tcl
+if ! grep -q '"../src/foo.sv"' vsim.tcl || grep -q "$DIR" vsim.tcl; then
+	cat vsim.tcl
+	echo "should emit paths relative to the working directory" >&2
+	exit 2
+fi
@@ -0,0 +1,49 @@
+#!/bin/bash
+set -e
+
+cd "$(dirname "${BASH_SOURCE[0]}")"
+BENDER="cargo run --"
+DIR="$(pwd)"/tmp/"$(basename $0 _test.sh)"
+[ ! -d "$DIR" ] || rm -rf "$DIR"
+mkdir -p "$DIR"
+cd "$DIR"
+
+mkdir src
+touch src/a.sv src/b.sv src/c.sv
+echo "
+package:
+  name: top
+
+sources:
+  - src/a.sv
+" > Bender.yml
+
+$BENDER script flist > /dev/null
+if [ ! -f .bender/sources_cache.json ]; then
+	echo "should write the source cache" >&2
+	exit 1
+fi
+
+# A cache hit uses the cached sources instead of resolving them again.
+sed -i 's#src/a.sv#src/b.sv#' .bender/sources_cache.json
+$BENDER script flist > flist
+if ! grep -q src/b.sv flist; then
+	cat flist
+	echo "should use the cached sources" >&2
+	exit 2
+fi
+$BENDER script flist --no-source-cache > flist
+if ! grep -q src/a.sv flist; then
+	cat flist
+	echo "should resolve the sources with --no-source-cache" >&2
+	exit 3
+fi
+
+# Changing the manifest invalidates the cache.
+sed -i 's#src/a.sv#src/c.sv#' Bender.yml
+$BENDER script flist > flist
+if ! grep -q src/c.sv flist || grep -q src/b.sv flist; then
+	cat flist
+	echo "should invalidate the cache when the manifest changes" >&2
+	exit 4
+fi
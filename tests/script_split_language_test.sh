@@ -0,0 +1,40 @@
+#!/bin/bash
+set -e
+
+cd "$(dirname "${BASH_SOURCE[0]}")"
+BENDER="cargo run --"
+DIR="$(pwd)"/tmp/"$(basename $0 _test.sh)"
+[ ! -d "$DIR" ] || rm -rf "$DIR"
+mkdir -p "$DIR"
+cd "$DIR"
+
+mkdir src
+touch src/top.sv src/pkg.vhd
+echo "
+package:
+  name: top
+
+sources:
+  - src/top.sv
+  - src/pkg.vhd
+" > Bender.yml
+
+# Each language is written to its own script.
+$BENDER script vsim --split-output-by-language --output-dir out
+for FILE in out/compile_verilog.tcl out/compile_vhdl.tcl; do
+	if [ ! -f "$FILE" ]; then
+		ls out
+		echo "should write $FILE" >&2
+		exit 1
+	fi
+done
+if ! grep -q 'top.sv' out/compile_verilog.tcl || grep -q 'pkg.vhd' out/compile_verilog.tcl; then
+	cat out/compile_verilog.tcl
+	echo "should only compile the Verilog sources in out/compile_verilog.tcl" >&2
+	exit 2
+fi
+if ! grep -q 'pkg.vhd' out/compile_vhdl.tcl || grep -q 'top.sv' out/compile_vhdl.tcl; then
+	cat out/compile_vhdl.tcl
+	echo "should only compile the VHDL sources in out/compile_vhdl.tcl" >&2
+	exit 3
+fi
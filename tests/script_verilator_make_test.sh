@@ -0,0 +1,55 @@
+#!/bin/bash
+set -e
+
+cd "$(dirname "${BASH_SOURCE[0]}")"
+BENDER="cargo run --"
+DIR="$(pwd)"/tmp/"$(basename $0 _test.sh)"
+[ ! -d "$DIR" ] || rm -rf "$DIR"
+mkdir -p "$DIR"
+cd "$DIR"
+
+# A dependency in a sibling directory whose name starts with the root.
+mkdir -p top/src top_ext/src
+touch top/src/top.sv top_ext/src/ext.sv
+echo "
+package:
+  name: ext
+
+sources:
+  - src/ext.sv
+" > top_ext/Bender.yml
+echo '
+package:
+  name: top
+
+dependencies:
+  ext: { path: ../top_ext }
+
+sources:
+  - defines:
+      PRICE: "$5#off"
+    files:
+      - src/top.sv
+' > top/Bender.yml
+
+cd top
+$BENDER script verilator-make --relative-path > frag.mk
+if ! grep -q "^    $DIR/top_ext/src/ext.sv" frag.mk; then
+	cat frag.mk
+	echo "should keep the path of the sibling dependency" >&2
+	exit 1
+fi
+if ! grep -q '^    $(ROOT)/src/top.sv' frag.mk; then
+	cat frag.mk
+	echo "should place root package files under \$(ROOT)" >&2
+	exit 2
+fi
+
+# The fragment parses as Make variable assignments.
+make -f frag.mk --eval 'all:;$(info $(VERILATOR_INPUT))$(info $(VERILATOR_DEFINES))@:' > make.out
+printf '%s\n' "$DIR/top_ext/src/ext.sv $DIR/top/src/top.sv" 'PRICE=$5#off TARGET_SYNTHESIS TARGET_VERILATOR' > expected
+if ! diff expected make.out; then
+	cat frag.mk
+	echo "should evaluate to the sources and defines" >&2
+	exit 3
+fi